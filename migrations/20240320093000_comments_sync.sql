@@ -0,0 +1,7 @@
+ALTER TABLE comments
+    ADD COLUMN database_id BIGINT UNIQUE,  -- databaseId of the comment on GitHub
+    ADD COLUMN created_at TIMESTAMPTZ,
+    ADD COLUMN updated_at TIMESTAMPTZ,
+    ADD COLUMN deleted BOOLEAN NOT NULL DEFAULT FALSE;  -- set when a re-sync no longer finds the comment
+
+CREATE INDEX comments_issue_id_idx ON comments (issue_id);
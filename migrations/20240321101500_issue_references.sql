@@ -0,0 +1,14 @@
+CREATE TABLE issue_references (
+    issue_id VARCHAR NOT NULL,  -- url of the referenced issue
+    source_id VARCHAR NOT NULL,  -- url of the pull_request, issue or commit that mentions the issue
+    source_type VARCHAR NOT NULL,  -- PullRequest, Issue or Commit
+    source_state VARCHAR NOT NULL,  -- OPEN, CLOSED, MERGED, empty for commits
+    source_repository VARCHAR NOT NULL,  -- url of the repo the reference came from
+    actor VARCHAR NOT NULL,
+    is_cross_repository BOOLEAN NOT NULL,
+    will_close_target BOOLEAN NOT NULL,
+    referenced_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (issue_id, source_id)
+);
+
+CREATE INDEX issue_references_source_id_idx ON issue_references (source_id);
@@ -0,0 +1,16 @@
+CREATE TYPE link_origin AS ENUM ('keyword', 'connected', 'cross_reference');
+
+CREATE TABLE pull_issue_links (
+    pull_id VARCHAR NOT NULL,  -- url of pull_request
+    issue_id VARCHAR NOT NULL,  -- url of the issue
+    origin link_origin NOT NULL,
+    PRIMARY KEY (pull_id, issue_id, origin)
+);
+
+CREATE INDEX pull_issue_links_issue_id_idx ON pull_issue_links (issue_id);
+
+INSERT INTO pull_issue_links (pull_id, issue_id, origin)
+SELECT source_id, issue_id, 'cross_reference'
+FROM issue_references
+WHERE source_type = 'PullRequest'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1 @@
+ALTER TYPE link_origin ADD VALUE 'mention';
@@ -0,0 +1,10 @@
+CREATE TABLE pull_request_reviews (
+    review_id VARCHAR PRIMARY KEY,  -- url of the review
+    pull_id VARCHAR NOT NULL,  -- url of pull_request
+    reviewer VARCHAR NOT NULL,
+    state VARCHAR NOT NULL,  -- APPROVED, CHANGES_REQUESTED, COMMENTED, DISMISSED or PENDING
+    submitted_at TIMESTAMPTZ,
+    author_association VARCHAR NOT NULL  -- MEMBER, OWNER, COLLABORATOR, CONTRIBUTOR, ...
+);
+
+CREATE INDEX pull_request_reviews_pull_id_idx ON pull_request_reviews (pull_id);
@@ -0,0 +1,9 @@
+ALTER TABLE pull_requests
+    ALTER COLUMN author DROP NOT NULL,  -- NULL when the account was deleted
+    ALTER COLUMN merged_by DROP NOT NULL,  -- NULL while the pull_request is not merged
+    ADD COLUMN labels TEXT[],
+    ADD COLUMN approving_reviewers TEXT[],
+    ADD COLUMN created_at TIMESTAMPTZ,
+    ADD COLUMN merged_at TIMESTAMPTZ;
+
+CREATE INDEX pull_requests_repository_idx ON pull_requests (repository);  -- repository matches projects.project_id
@@ -0,0 +1,13 @@
+ALTER TABLE pull_requests
+    ADD COLUMN additions INT,
+    ADD COLUMN deletions INT,
+    ADD COLUMN changed_files INT,
+    ADD COLUMN commit_count INT;
+
+CREATE TABLE pull_request_files (
+    pull_id VARCHAR NOT NULL,  -- url of pull_request
+    path VARCHAR NOT NULL,
+    additions INT NOT NULL,
+    deletions INT NOT NULL,
+    PRIMARY KEY (pull_id, path)
+);
@@ -0,0 +1,12 @@
+ALTER TABLE pull_requests
+    ADD COLUMN ci_commit VARCHAR,  -- url of the commit the checks ran on, the merge commit when it has any
+    ADD COLUMN ci_state VARCHAR;  -- statusCheckRollup state: SUCCESS, FAILURE, PENDING, ERROR or EXPECTED
+
+CREATE TABLE pull_request_checks (
+    pull_id VARCHAR NOT NULL,  -- url of pull_request
+    name VARCHAR NOT NULL,  -- CheckRun name or StatusContext context
+    kind VARCHAR NOT NULL,  -- CheckRun or StatusContext
+    state VARCHAR NOT NULL,
+    is_required BOOLEAN NOT NULL,
+    PRIMARY KEY (pull_id, kind, name)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE issue_assignment_events (
+    event_id VARCHAR PRIMARY KEY,  -- node id of the AssignedEvent or UnassignedEvent
+    issue_id VARCHAR NOT NULL,  -- url of the issue
+    assignee VARCHAR NOT NULL,
+    actor VARCHAR NOT NULL,  -- who made the change, empty for deleted accounts
+    event VARCHAR NOT NULL,  -- assigned or unassigned
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX issue_assignment_events_issue_id_idx ON issue_assignment_events (issue_id, created_at);
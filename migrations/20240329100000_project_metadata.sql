@@ -0,0 +1,11 @@
+ALTER TABLE projects
+    ADD COLUMN description TEXT,
+    ADD COLUMN primary_language VARCHAR,
+    ADD COLUMN languages TEXT[],
+    ADD COLUMN topics TEXT[],
+    ADD COLUMN license VARCHAR,  -- spdx id
+    ADD COLUMN stars BIGINT,
+    ADD COLUMN is_archived BOOLEAN,
+    ADD COLUMN is_fork BOOLEAN,
+    ADD COLUMN default_branch VARCHAR,
+    ADD COLUMN opted_in BOOLEAN;  -- NULL until the repo metadata was checked against the campaign opt-in rule
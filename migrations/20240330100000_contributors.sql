@@ -0,0 +1,42 @@
+CREATE TABLE contributors (
+    login VARCHAR PRIMARY KEY,
+    database_id BIGINT,
+    name VARCHAR,
+    company VARCHAR,
+    location VARCHAR,
+    followers BIGINT,  -- NULL for organisations
+    account_created_at TIMESTAMPTZ,
+    avatar_url VARCHAR,
+    is_bot BOOLEAN NOT NULL DEFAULT FALSE,
+    is_organization BOOLEAN NOT NULL DEFAULT FALSE,
+    fetched_at TIMESTAMPTZ  -- NULL while the row is only a stub holding the login
+);
+
+ALTER TABLE issues
+    ADD COLUMN issue_author VARCHAR;
+
+-- deleted accounts show up as an empty login in rows synced before, GitHub names them ghost
+UPDATE comments SET creator = 'ghost' WHERE creator = '';
+UPDATE pull_requests SET author = NULL WHERE author = '';
+UPDATE pull_requests SET merged_by = NULL WHERE merged_by = '';
+UPDATE issues SET issue_assignee = NULL WHERE issue_assignee = '';
+
+INSERT INTO contributors (login, is_bot)
+SELECT login, login LIKE '%[bot]'
+FROM (
+    SELECT creator AS login FROM comments
+    UNION SELECT author FROM pull_requests
+    UNION SELECT merged_by FROM pull_requests
+    UNION SELECT issue_assignee FROM issues
+) logins
+WHERE login IS NOT NULL
+ON CONFLICT (login) DO NOTHING;
+
+ALTER TABLE comments
+    ADD CONSTRAINT comments_creator_fkey FOREIGN KEY (creator) REFERENCES contributors (login);
+ALTER TABLE pull_requests
+    ADD CONSTRAINT pull_requests_author_fkey FOREIGN KEY (author) REFERENCES contributors (login),
+    ADD CONSTRAINT pull_requests_merged_by_fkey FOREIGN KEY (merged_by) REFERENCES contributors (login);
+ALTER TABLE issues
+    ADD CONSTRAINT issues_issue_author_fkey FOREIGN KEY (issue_author) REFERENCES contributors (login),
+    ADD CONSTRAINT issues_issue_assignee_fkey FOREIGN KEY (issue_assignee) REFERENCES contributors (login);
@@ -0,0 +1,3 @@
+ALTER TABLE pull_requests
+    ADD COLUMN spam_score INT,  -- NULL until scored, higher is more likely spam
+    ADD COLUMN spam_reasons TEXT[];
@@ -0,0 +1,9 @@
+CREATE TABLE eligibility_results (
+    item_id VARCHAR NOT NULL,  -- url of the pull_request or issue
+    item_type VARCHAR NOT NULL,  -- pull_request or issue
+    rule_name VARCHAR NOT NULL,
+    passed BOOLEAN NOT NULL,
+    failed TEXT[] NOT NULL,  -- descriptions of the predicates that made the rule fail
+    evaluated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (item_id, rule_name)
+);
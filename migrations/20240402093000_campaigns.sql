@@ -0,0 +1,41 @@
+CREATE TABLE campaigns (
+    campaign_id VARCHAR PRIMARY KEY,  -- slug, e.g. hacktoberfest-2024
+    name VARCHAR NOT NULL,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,  -- inclusive
+    issue_labels TEXT[] NOT NULL,
+    accepted_pr_labels TEXT[] NOT NULL,
+    excluded_labels TEXT[] NOT NULL,
+    eligible_repos TEXT[] NOT NULL,  -- urls, empty means any repo that opted in
+    opt_in_topic VARCHAR NOT NULL,
+    budget_pool BIGINT NOT NULL  -- total budget the approved issues of the campaign may share
+);
+
+-- everything tracked so far came from the hard-coded hacktoberfest 2023 search
+INSERT INTO campaigns (campaign_id, name, starts_on, ends_on, issue_labels, accepted_pr_labels, excluded_labels, eligible_repos, opt_in_topic, budget_pool)
+VALUES ('hacktoberfest-2023', 'Hacktoberfest 2023', '2023-10-01', '2023-10-31',
+    ARRAY['hacktoberfest'], ARRAY['hacktoberfest-accepted'], ARRAY['spam', 'invalid'],
+    ARRAY[]::text[], 'hacktoberfest', 0);
+
+-- a repo can take part in several campaigns, issues and pull_requests belong to exactly one
+CREATE TABLE campaign_projects (
+    campaign_id VARCHAR NOT NULL REFERENCES campaigns (campaign_id),
+    project_id VARCHAR NOT NULL REFERENCES projects (project_id),
+    PRIMARY KEY (campaign_id, project_id)
+);
+
+INSERT INTO campaign_projects (campaign_id, project_id)
+SELECT 'hacktoberfest-2023', project_id FROM projects;
+
+ALTER TABLE issues
+    ADD COLUMN campaign_id VARCHAR REFERENCES campaigns (campaign_id);
+ALTER TABLE pull_requests
+    ADD COLUMN campaign_id VARCHAR REFERENCES campaigns (campaign_id);
+
+UPDATE issues SET campaign_id = 'hacktoberfest-2023';
+UPDATE pull_requests SET campaign_id = 'hacktoberfest-2023';
+
+ALTER TABLE issues
+    ALTER COLUMN campaign_id SET NOT NULL;
+ALTER TABLE pull_requests
+    ALTER COLUMN campaign_id SET NOT NULL;
@@ -0,0 +1,12 @@
+ALTER TABLE campaigns
+    ADD COLUMN window_days INT NOT NULL DEFAULT 3,  -- days covered by one search query
+    ADD COLUMN search_scopes TEXT[] NOT NULL DEFAULT ARRAY[]::text[],  -- org:x or repo:x/y qualifiers, empty searches all of GitHub
+    ADD COLUMN sync_interval_hours INT;  -- NULL when the campaign is only synced by hand
+
+CREATE TABLE campaign_rules (
+    campaign_id VARCHAR NOT NULL REFERENCES campaigns (campaign_id),
+    rule_name VARCHAR NOT NULL,
+    applies_to VARCHAR NOT NULL,  -- pull_request or issue
+    predicate JSONB NOT NULL,  -- the predicate tree as written in the campaign file
+    PRIMARY KEY (campaign_id, rule_name)
+);
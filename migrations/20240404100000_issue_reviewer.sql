@@ -0,0 +1,9 @@
+ALTER TABLE issues
+    ADD COLUMN reviewed_by VARCHAR,  -- who made the last review decision, as given to --reviewer
+    ADD COLUMN reviewed_at TIMESTAMPTZ,
+    ALTER COLUMN review_status SET DEFAULT 'queue';
+
+-- issues stored so far were never reviewed
+UPDATE issues SET review_status = 'queue' WHERE review_status IS NULL;
+
+CREATE INDEX issues_review_status_idx ON issues (campaign_id, review_status);
@@ -0,0 +1,26 @@
+CREATE TYPE sync_run_status AS ENUM ('running', 'failed', 'finished');
+CREATE TYPE sync_search AS ENUM ('open_issues', 'closed_issues', 'pull_requests');
+
+CREATE TABLE sync_runs (
+    run_id SERIAL PRIMARY KEY,
+    campaign_id VARCHAR NOT NULL REFERENCES campaigns (campaign_id),
+    target VARCHAR NOT NULL,  -- what was synced, issues or prs
+    status sync_run_status NOT NULL DEFAULT 'running',  -- a run killed mid-way stays running
+    plan TEXT[] NOT NULL,  -- the search query of every window, in the order they run
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ,
+    error TEXT  -- why the run failed
+);
+
+CREATE TABLE sync_run_windows (
+    run_id INT NOT NULL REFERENCES sync_runs (run_id),
+    window_index INT NOT NULL,
+    search sync_search NOT NULL,
+    query TEXT NOT NULL,
+    cursor TEXT,  -- where the next page starts, NULL before the first page
+    pages INT NOT NULL DEFAULT 0,  -- pages fetched and stored so far
+    finished BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (run_id, window_index)
+);
+
+CREATE INDEX sync_runs_campaign_id_idx ON sync_runs (campaign_id, target, run_id);
@@ -0,0 +1,10 @@
+CREATE TABLE sync_watermarks (
+    campaign_id VARCHAR NOT NULL REFERENCES campaigns (campaign_id),
+    query TEXT NOT NULL,  -- the window's search query without the updated: qualifier
+    updated_at TIMESTAMPTZ NOT NULL,  -- newest updatedAt seen by a finished run
+    PRIMARY KEY (campaign_id, query)
+);
+
+ALTER TABLE sync_run_windows
+    ADD COLUMN since TIMESTAMPTZ,  -- watermark the window searches from, NULL fetches everything
+    ADD COLUMN max_updated_at TIMESTAMPTZ;  -- newest updatedAt fetched so far
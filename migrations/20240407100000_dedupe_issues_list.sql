@@ -0,0 +1,11 @@
+-- repeated syncs appended the same issue to issues_list again, keep the first of each
+UPDATE projects
+SET issues_list = ARRAY(
+    SELECT issue_id
+    FROM unnest(issues_list) WITH ORDINALITY AS t(issue_id, position)
+    GROUP BY issue_id
+    ORDER BY min(position)
+)
+WHERE cardinality(issues_list) > (
+    SELECT count(DISTINCT issue_id) FROM unnest(issues_list) AS t(issue_id)
+);
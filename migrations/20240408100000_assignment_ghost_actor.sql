@@ -0,0 +1,4 @@
+-- deleted accounts are stored as 'ghost' like everywhere else, not as an empty actor
+UPDATE issue_assignment_events
+SET actor = 'ghost'
+WHERE actor = '';
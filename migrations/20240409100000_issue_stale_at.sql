@@ -0,0 +1,5 @@
+-- when the claim was flagged, an assignment after it releases the flag
+ALTER TABLE issues
+    ADD COLUMN stale_at TIMESTAMPTZ;
+
+UPDATE issues SET stale_at = NOW() WHERE issue_status = 'stale';
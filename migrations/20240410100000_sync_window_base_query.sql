@@ -0,0 +1,4 @@
+-- windows split off at the search cap search a narrower created: range than the query they were
+-- planned for, their watermark is kept under the planned one
+ALTER TABLE sync_run_windows
+    ADD COLUMN base_query TEXT;  -- the planned query of a split window, NULL for planned windows
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+
+// REST logins of GitHub Apps carry it, GraphQL drops it and reports the author as a Bot instead
+pub const BOT_SUFFIX: &str = "[bot]";
+
+// GraphQL login of an author as stored everywhere else, "dependabot" typed Bot becomes
+// "dependabot[bot]" so the same account gets one login whichever API it came from
+pub fn bot_login(login: String, typename: Option<&str>) -> String {
+    if typename == Some("Bot") && !login.ends_with(BOT_SUFFIX) {
+        format!("{login}{BOT_SUFFIX}")
+    } else {
+        login
+    }
+}
+
+// accounts detected as bots are left out of eligibility, leaderboards and assignee inference;
+// `deny` adds automation running under a user account, `allow` lets a bot back in
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BotFilter {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl BotFilter {
+    // comma separated logins in BOT_ALLOWLIST and BOT_DENYLIST
+    pub fn from_env() -> Self {
+        let logins = |key: &str| {
+            env::var(key)
+                .unwrap_or_default()
+                .split(',')
+                .map(|login| login.trim().to_string())
+                .filter(|login| !login.is_empty())
+                .collect::<Vec<String>>()
+        };
+
+        BotFilter {
+            allow: logins("BOT_ALLOWLIST"),
+            deny: logins("BOT_DENYLIST"),
+        }
+    }
+
+    pub fn is_bot(&self, login: &str) -> bool {
+        let listed = |list: &[String]| list.iter().any(|l| l.eq_ignore_ascii_case(login));
+
+        if listed(&self.deny) {
+            true
+        } else if listed(&self.allow) {
+            false
+        } else {
+            login.ends_with(BOT_SUFFIX)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(allow: &[&str], deny: &[&str]) -> BotFilter {
+        BotFilter {
+            allow: allow.iter().map(|l| l.to_string()).collect(),
+            deny: deny.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn graphql_bots_get_the_rest_suffix() {
+        assert_eq!(
+            bot_login("dependabot".into(), Some("Bot")),
+            "dependabot[bot]"
+        );
+        assert_eq!(
+            bot_login("dependabot[bot]".into(), Some("Bot")),
+            "dependabot[bot]"
+        );
+        assert_eq!(bot_login("octocat".into(), Some("User")), "octocat");
+        assert_eq!(bot_login("octocat".into(), None), "octocat");
+    }
+
+    #[test]
+    fn detects_bots_by_suffix() {
+        let bots = BotFilter::default();
+        assert!(bots.is_bot("renovate[bot]"));
+        assert!(!bots.is_bot("octocat"));
+    }
+
+    #[test]
+    fn deny_list_marks_user_accounts_as_bots() {
+        assert!(filter(&[], &["CI-Runner"]).is_bot("ci-runner"));
+    }
+
+    #[test]
+    fn allow_list_lets_a_bot_back_in() {
+        assert!(!filter(&["Renovate[bot]"], &[]).is_bot("renovate[bot]"));
+    }
+
+    #[test]
+    fn deny_list_wins_over_allow_list() {
+        assert!(filter(&["octocat"], &["octocat"]).is_bot("octocat"));
+    }
+}
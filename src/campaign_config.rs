@@ -0,0 +1,428 @@
+use chrono::NaiveDate;
+
+use crate::campaigns::{Campaign, DEFAULT_WINDOW_DAYS};
+use crate::db_updater_local::{sync_campaign_rules, upsert_campaign};
+use crate::eligibility::EligibilityRule;
+use crate::repository_metadata::DEFAULT_OPT_IN_TOPIC;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use std::collections::HashSet;
+use std::path::Path;
+
+// bumped whenever the file layout changes in a way older files can't be read as
+pub const CONFIG_VERSION: i64 = 1;
+
+// a campaign file looks like
+//
+//     version = 1
+//
+//     [[campaigns]]
+//     id = "hacktoberfest-2024"
+//     name = "Hacktoberfest 2024"
+//     starts_on = 2024-10-01
+//     ends_on = 2024-10-31
+//     issue_labels = ["hacktoberfest"]
+//     accepted_pr_labels = ["hacktoberfest-accepted"]
+//     excluded_labels = ["spam", "invalid"]
+//     search_scopes = ["org:WasmEdge"]
+//     sync_interval_hours = 6
+//
+//     [[campaigns.rules]]
+//     name = "accepted"
+//     applies_to = "pull_request"
+//     predicate = { all = [{ merged = true }, { created_after = "2024-10-01T00:00:00Z" }] }
+//
+// dates inside predicates are quoted, the campaign dates are plain TOML dates
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CampaignFile {
+    pub version: i64,
+    #[serde(default)]
+    pub campaigns: Vec<CampaignDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CampaignDefinition {
+    pub id: String,
+    pub name: String,
+    pub starts_on: toml::value::Datetime,
+    pub ends_on: toml::value::Datetime,
+    #[serde(default)]
+    pub issue_labels: Vec<String>,
+    pub accepted_pr_labels: Vec<String>,
+    #[serde(default)]
+    pub excluded_labels: Vec<String>,
+    #[serde(default)]
+    pub eligible_repos: Vec<String>,
+    pub opt_in_topic: Option<String>,
+    #[serde(default)]
+    pub budget_pool: i64,
+    pub window_days: Option<i32>,
+    #[serde(default)]
+    pub search_scopes: Vec<String>,
+    pub sync_interval_hours: Option<i32>,
+    #[serde(default)]
+    pub rules: Vec<EligibilityRule>,
+}
+
+fn to_date(value: &toml::value::Datetime) -> Result<NaiveDate, String> {
+    match (&value.date, &value.time, &value.offset) {
+        (Some(date), None, None) => NaiveDate::from_ymd_opt(
+            i32::from(date.year),
+            u32::from(date.month),
+            u32::from(date.day),
+        )
+        .ok_or_else(|| format!("{value} is not a calendar date")),
+        _ => Err(format!("expected a date like 2024-10-01, found {value}")),
+    }
+}
+
+fn is_slug(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+fn is_repo_url(url: &str) -> bool {
+    url.strip_prefix("https://github.com/")
+        .map(|path| path.trim_end_matches('/').split('/').collect::<Vec<&str>>())
+        .is_some_and(|parts| parts.len() == 2 && parts.iter().all(|p| !p.is_empty()))
+}
+
+impl CampaignDefinition {
+    // every problem found, each prefixed with where it is, so one run points at all of them
+    fn validate(&self, index: usize) -> Result<(Campaign, Vec<EligibilityRule>), Vec<String>> {
+        let at = format!("campaigns[{index}] ({})", self.id);
+        let mut errors = Vec::new();
+
+        if !is_slug(&self.id) {
+            errors.push(format!(
+                "{at}: id must be lowercase letters, digits and dashes"
+            ));
+        }
+        if self.name.trim().is_empty() {
+            errors.push(format!("{at}: name is empty"));
+        }
+
+        let mut date = |field: &str, value: &toml::value::Datetime| match to_date(value) {
+            Ok(date) => Some(date),
+            Err(e) => {
+                errors.push(format!("{at}: {field} {e}"));
+                None
+            }
+        };
+        let starts_on = date("starts_on", &self.starts_on);
+        let ends_on = date("ends_on", &self.ends_on);
+        if let (Some(starts_on), Some(ends_on)) = (starts_on, ends_on) {
+            if ends_on < starts_on {
+                errors.push(format!(
+                    "{at}: ends_on {ends_on} is before starts_on {starts_on}"
+                ));
+            }
+        }
+
+        if self.issue_labels.is_empty() {
+            errors.push(format!("{at}: issue_labels needs at least one label"));
+        }
+        if self.accepted_pr_labels.is_empty() {
+            errors.push(format!("{at}: accepted_pr_labels needs at least one label"));
+        }
+        for (field, labels) in [
+            ("issue_labels", &self.issue_labels),
+            ("accepted_pr_labels", &self.accepted_pr_labels),
+            ("excluded_labels", &self.excluded_labels),
+        ] {
+            for (i, label) in labels.iter().enumerate() {
+                if label.trim().is_empty() {
+                    errors.push(format!("{at}: {field}[{i}] is empty"));
+                } else if label.contains(char::is_whitespace) || label.contains(',') {
+                    errors.push(format!(
+                        "{at}: {field}[{i}] \"{label}\" can't be searched, it has spaces or commas"
+                    ));
+                }
+            }
+        }
+        for label in &self.excluded_labels {
+            if self.accepted_pr_labels.contains(label) {
+                errors.push(format!(
+                    "{at}: \"{label}\" is both an accepted and an excluded label"
+                ));
+            }
+        }
+
+        for (i, repo) in self.eligible_repos.iter().enumerate() {
+            if !is_repo_url(repo) {
+                errors.push(format!(
+                    "{at}: eligible_repos[{i}] \"{repo}\" is not https://github.com/owner/repo"
+                ));
+            }
+        }
+        for (i, scope) in self.search_scopes.iter().enumerate() {
+            let valid = ["org:", "repo:", "user:"].iter().any(|prefix| {
+                scope
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| !rest.is_empty())
+            });
+            if !valid {
+                errors.push(format!(
+                    "{at}: search_scopes[{i}] \"{scope}\" must be org:, repo: or user: and a name"
+                ));
+            }
+        }
+
+        if self.budget_pool < 0 {
+            errors.push(format!("{at}: budget_pool can't be negative"));
+        }
+        if let Some(window_days) = self.window_days {
+            if window_days < 1 {
+                errors.push(format!("{at}: window_days must be at least 1"));
+            }
+        }
+        if let Some(hours) = self.sync_interval_hours {
+            if hours < 1 {
+                errors.push(format!("{at}: sync_interval_hours must be at least 1"));
+            }
+        }
+
+        let mut rule_names = HashSet::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.name.trim().is_empty() {
+                errors.push(format!("{at}: rules[{i}] has no name"));
+            } else if !rule_names.insert(rule.name.as_str()) {
+                errors.push(format!(
+                    "{at}: rules[{i}] repeats the name \"{}\"",
+                    rule.name
+                ));
+            }
+            if rule.campaign_id.as_deref().is_some_and(|id| id != self.id) {
+                errors.push(format!(
+                    "{at}: rules[{i}] \"{}\" names another campaign",
+                    rule.name
+                ));
+            }
+        }
+
+        match (starts_on, ends_on) {
+            (Some(starts_on), Some(ends_on)) if errors.is_empty() => Ok((
+                Campaign {
+                    campaign_id: self.id.clone(),
+                    name: self.name.clone(),
+                    starts_on,
+                    ends_on,
+                    issue_labels: self.issue_labels.clone(),
+                    accepted_pr_labels: self.accepted_pr_labels.clone(),
+                    excluded_labels: self.excluded_labels.clone(),
+                    eligible_repos: self
+                        .eligible_repos
+                        .iter()
+                        .map(|repo| repo.trim_end_matches('/').to_string())
+                        .collect(),
+                    opt_in_topic: self
+                        .opt_in_topic
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_OPT_IN_TOPIC.to_string()),
+                    budget_pool: self.budget_pool,
+                    window_days: self.window_days.unwrap_or(DEFAULT_WINDOW_DAYS),
+                    search_scopes: self.search_scopes.clone(),
+                    sync_interval_hours: self.sync_interval_hours,
+                },
+                self.rules
+                    .iter()
+                    .cloned()
+                    .map(|rule| EligibilityRule {
+                        campaign_id: Some(self.id.clone()),
+                        ..rule
+                    })
+                    .collect(),
+            )),
+            _ => Err(errors),
+        }
+    }
+}
+
+// parses and validates a campaign file, the error lists every problem found
+pub fn parse_campaign_file(content: &str) -> anyhow::Result<Vec<(Campaign, Vec<EligibilityRule>)>> {
+    let file: CampaignFile = toml::from_str(content).map_err(|e| anyhow!("{}", e))?;
+
+    if file.version != CONFIG_VERSION {
+        return Err(anyhow!(
+            "version {} is not supported, expected {}",
+            file.version,
+            CONFIG_VERSION
+        ));
+    }
+
+    let mut errors = Vec::new();
+    let mut campaigns = Vec::new();
+    let mut ids = HashSet::new();
+    for (index, definition) in file.campaigns.iter().enumerate() {
+        if !ids.insert(definition.id.as_str()) {
+            errors.push(format!(
+                "campaigns[{index}] ({}): id is already used by an earlier campaign",
+                definition.id
+            ));
+        }
+        match definition.validate(index) {
+            Ok(campaign) => campaigns.push(campaign),
+            Err(found) => errors.extend(found),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(campaigns)
+    } else {
+        Err(anyhow!("{}", errors.join("\n")))
+    }
+}
+
+pub fn load_campaign_file(path: &Path) -> anyhow::Result<Vec<(Campaign, Vec<EligibilityRule>)>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+
+    parse_campaign_file(&content).map_err(|e| anyhow!("{}:\n{}", path.display(), e))
+}
+
+// `campaign apply`: writes every campaign of the file and its rules to the database, campaigns
+// missing from the file are left alone since issues and pull_requests still point at them
+pub async fn apply_campaign_file(pool: &PgPool, path: &Path) -> anyhow::Result<Vec<Campaign>> {
+    let definitions = load_campaign_file(path)?;
+
+    for (campaign, rules) in &definitions {
+        upsert_campaign(pool, campaign).await?;
+        sync_campaign_rules(pool, &campaign.campaign_id, rules).await?;
+    }
+
+    Ok(definitions
+        .into_iter()
+        .map(|(campaign, _)| campaign)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMPAIGN: &str = r#"
+        version = 1
+
+        [[campaigns]]
+        id = "hacktoberfest-2024"
+        name = "Hacktoberfest 2024"
+        starts_on = 2024-10-01
+        ends_on = 2024-10-31
+        issue_labels = ["hacktoberfest"]
+        accepted_pr_labels = ["hacktoberfest-accepted"]
+        excluded_labels = ["spam"]
+        eligible_repos = ["https://github.com/WasmEdge/WasmEdge/"]
+        search_scopes = ["org:WasmEdge"]
+        sync_interval_hours = 6
+
+        [[campaigns.rules]]
+        name = "accepted"
+        applies_to = "pull_request"
+        predicate = { merged = true }
+    "#;
+
+    fn errors(content: &str) -> Vec<String> {
+        parse_campaign_file(content)
+            .unwrap_err()
+            .to_string()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn parses_a_campaign_and_scopes_its_rules() {
+        let campaigns = parse_campaign_file(CAMPAIGN).unwrap();
+        assert_eq!(campaigns.len(), 1);
+
+        let (campaign, rules) = &campaigns[0];
+        assert_eq!(campaign.campaign_id, "hacktoberfest-2024");
+        assert_eq!(
+            campaign.starts_on,
+            NaiveDate::from_ymd_opt(2024, 10, 1).unwrap()
+        );
+        assert_eq!(
+            campaign.eligible_repos,
+            vec!["https://github.com/WasmEdge/WasmEdge"]
+        );
+        assert_eq!(campaign.opt_in_topic, DEFAULT_OPT_IN_TOPIC);
+        assert_eq!(campaign.window_days, DEFAULT_WINDOW_DAYS);
+        assert_eq!(campaign.sync_interval_hours, Some(6));
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].campaign_id.as_deref(), Some("hacktoberfest-2024"));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let content = CAMPAIGN.replace("version = 1", "version = 2");
+        assert_eq!(
+            errors(&content),
+            vec!["version 2 is not supported, expected 1"]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let content = CAMPAIGN.replace("sync_interval_hours", "sync_every_hours");
+        assert!(parse_campaign_file(&content).is_err());
+    }
+
+    #[test]
+    fn lists_every_problem_of_a_campaign() {
+        let content = CAMPAIGN
+            .replace(
+                "hacktoberfest-2024\"",
+                "Hacktoberfest 2024\"\n        window_days = 0",
+            )
+            .replace("ends_on = 2024-10-31", "ends_on = 2024-09-30")
+            .replace("issue_labels = [\"hacktoberfest\"]", "issue_labels = []")
+            .replace(
+                "[\"spam\"]",
+                "[\"hacktoberfest-accepted\", \"good first issue\"]",
+            )
+            .replace("org:WasmEdge", "WasmEdge");
+
+        let at = "campaigns[0] (Hacktoberfest 2024)";
+        assert_eq!(
+            errors(&content),
+            vec![
+                format!("{at}: id must be lowercase letters, digits and dashes"),
+                format!("{at}: ends_on 2024-09-30 is before starts_on 2024-10-01"),
+                format!("{at}: issue_labels needs at least one label"),
+                format!(
+                    "{at}: excluded_labels[1] \"good first issue\" can't be searched, it has spaces or commas"
+                ),
+                format!("{at}: \"hacktoberfest-accepted\" is both an accepted and an excluded label"),
+                format!("{at}: search_scopes[0] \"WasmEdge\" must be org:, repo: or user: and a name"),
+                format!("{at}: window_days must be at least 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_repeated_ids() {
+        let content = format!(
+            "{CAMPAIGN}\n{}",
+            &CAMPAIGN[CAMPAIGN.find("[[campaigns]]").unwrap()..]
+        );
+        assert_eq!(
+            errors(&content),
+            vec!["campaigns[1] (hacktoberfest-2024): id is already used by an earlier campaign"]
+        );
+    }
+
+    #[test]
+    fn campaign_dates_must_be_plain_dates() {
+        let content =
+            CAMPAIGN.replace("starts_on = 2024-10-01", "starts_on = 2024-10-01T00:00:00Z");
+        assert_eq!(
+            errors(&content),
+            vec!["campaigns[0] (hacktoberfest-2024): starts_on expected a date like 2024-10-01, found 2024-10-01T00:00:00Z"]
+        );
+    }
+}
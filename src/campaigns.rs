@@ -0,0 +1,120 @@
+use chrono::{Duration, NaiveDate};
+
+use crate::repository_metadata::DEFAULT_OPT_IN_TOPIC;
+use serde::{Deserialize, Serialize};
+
+// seeded by the migration, rows stored before campaigns existed belong to it
+pub const DEFAULT_CAMPAIGN_ID: &str = "hacktoberfest-2023";
+
+// days covered by one search query, keeps each search under GitHub's 1000 result cap
+pub const DEFAULT_WINDOW_DAYS: i32 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Campaign {
+    pub campaign_id: String, // slug, e.g. hacktoberfest-2024
+    pub name: String,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate, // inclusive
+    pub issue_labels: Vec<String>,
+    pub accepted_pr_labels: Vec<String>,
+    pub excluded_labels: Vec<String>,
+    pub eligible_repos: Vec<String>, // urls, empty means any repo that opted in
+    pub opt_in_topic: String,
+    pub budget_pool: i64, // total budget the approved issues may share, 0 for no limit
+    pub window_days: i32,
+    pub search_scopes: Vec<String>, // "org:x" or "repo:x/y" qualifiers, empty searches all of GitHub
+    pub sync_interval_hours: Option<i32>, // for `sync --if-due`, None syncs on every call
+}
+
+impl Campaign {
+    pub fn hacktoberfest(year: i32) -> Self {
+        Campaign {
+            campaign_id: format!("hacktoberfest-{year}"),
+            name: format!("Hacktoberfest {year}"),
+            starts_on: NaiveDate::from_ymd_opt(year, 10, 1).expect("valid date"),
+            ends_on: NaiveDate::from_ymd_opt(year, 10, 31).expect("valid date"),
+            issue_labels: vec![String::from("hacktoberfest")],
+            accepted_pr_labels: vec![String::from("hacktoberfest-accepted")],
+            excluded_labels: vec![String::from("spam"), String::from("invalid")],
+            eligible_repos: Vec::new(),
+            opt_in_topic: DEFAULT_OPT_IN_TOPIC.to_string(),
+            budget_pool: 0,
+            window_days: DEFAULT_WINDOW_DAYS,
+            search_scopes: Vec::new(),
+            sync_interval_hours: None,
+        }
+    }
+
+    pub fn accepts_repository(&self, repository: &str) -> bool {
+        self.eligible_repos.is_empty()
+            || self
+                .eligible_repos
+                .iter()
+                .any(|repo| repo.trim_end_matches('/').eq_ignore_ascii_case(repository))
+    }
+
+    // "2023-10-01..2023-10-03" style windows of window_days covering the campaign, both ends are
+    // inclusive in GitHub search so consecutive windows don't overlap
+    pub fn date_ranges(&self) -> Vec<String> {
+        let mut ranges = Vec::new();
+        let mut start = self.starts_on;
+        while start <= self.ends_on {
+            let end =
+                (start + Duration::days(i64::from(self.window_days.max(1)) - 1)).min(self.ends_on);
+            ranges.push(format!(
+                "{}..{}",
+                start.format("%Y-%m-%d"),
+                end.format("%Y-%m-%d")
+            ));
+            start = end + Duration::days(1);
+        }
+        ranges
+    }
+
+    // the search qualifiers shared by every query, one entry per scope
+    fn scoped(&self, qualifiers: String) -> Vec<String> {
+        let excluded = self
+            .excluded_labels
+            .iter()
+            .map(|label| format!(" -label:{label}"))
+            .collect::<String>();
+
+        if self.search_scopes.is_empty() {
+            vec![format!("{qualifiers}{excluded}")]
+        } else {
+            self.search_scopes
+                .iter()
+                .map(|scope| format!("{scope} {qualifiers}{excluded}"))
+                .collect()
+        }
+    }
+
+    // search strings for the merged, approved pull_requests carrying an accepted label
+    pub fn pull_request_queries(&self) -> Vec<String> {
+        self.date_ranges()
+            .into_iter()
+            .flat_map(|date_range| {
+                self.scoped(format!(
+                    "label:{} is:pr is:merged created:{date_range} review:approved",
+                    self.accepted_pr_labels.join(",")
+                ))
+            })
+            .collect()
+    }
+
+    // search strings for the campaign's open or closed issues; assigned ones are kept in, their
+    // assignment history is what stale claims are found from
+    pub fn issue_queries(&self, open: bool) -> Vec<String> {
+        let state = if open { "is:open" } else { "is:closed" };
+
+        self.date_ranges()
+            .into_iter()
+            .flat_map(|date_range| {
+                self.scoped(format!(
+                    "label:{} is:issue {state} created:{date_range}",
+                    self.issue_labels.join(",")
+                ))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,956 @@
+use crate::bots::BotFilter;
+use crate::campaign_config::apply_campaign_file;
+use crate::campaigns::{Campaign, DEFAULT_CAMPAIGN_ID};
+use crate::contributors::GHOST_LOGIN;
+use crate::db_ops::*;
+use crate::db_updater_local::*;
+use crate::dry_run::{connect_dry_run, diff_snapshots, print_diff, rollback, take_snapshot};
+use crate::eligibility::{default_rules, parse_rules, run_eligibility_rules};
+use crate::issue_search_closed::{search_issues_closed_page, OuterIssue as ClosedIssue};
+use crate::issues_tracker_local::{get_rate_limit_remaining, search_issues_open_page, OuterIssue};
+use crate::listing::IssueFilter;
+use crate::output::{render, select_columns, sort_rows, OutputFormat, Row};
+use crate::pull_request_overall_search::{overall_search_pull_requests_page, OuterPull};
+use crate::review_queue::run_review_queue;
+use crate::stale_claims::{run_stale_claim_check, DEFAULT_STALE_DAYS};
+use crate::sync_runs::{
+    plan_windows, window_concurrency, SeenNodes, SyncRun, SyncRunStatus, SyncSearch, SyncWindow,
+    DEFAULT_SYNC_CONCURRENCY, SEARCH_PAGE_LIMIT,
+};
+use chrono::{Duration, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use futures::stream::{self, TryStreamExt};
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicI32;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "the_tracker",
+    about = "Tracks campaign issues, pull requests and payouts"
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Args, Debug)]
+pub struct GlobalArgs {
+    #[arg(
+        long,
+        global = true,
+        env = "TRACKER_CONFIG",
+        default_value = "campaigns.toml",
+        help = "Campaign definition file"
+    )]
+    pub config: PathBuf,
+
+    #[arg(
+        long,
+        global = true,
+        env = "CAMPAIGN",
+        default_value = DEFAULT_CAMPAIGN_ID,
+        help = "Campaign to work on"
+    )]
+    pub campaign: String,
+
+    // a global argument can't be required, a missing url is reported by run
+    #[arg(
+        long,
+        global = true,
+        env = "DATABASE_URL",
+        hide_env_values = true,
+        help = "Postgres connection string"
+    )]
+    pub database_url: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Print progress to stderr"
+    )]
+    pub verbose: u8,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Fetch and reconcile as usual, print what would change and write nothing"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long,
+        global = true,
+        env = "REVIEWER",
+        help = "Name recorded with review decisions, defaults to $USER"
+    )]
+    pub reviewer: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "Fetch from GitHub into the database")]
+    Sync {
+        #[arg(value_enum)]
+        what: SyncTarget,
+        #[command(flatten)]
+        options: SyncOptions,
+    },
+    #[command(about = "List stored projects, issues or pull requests of the campaign")]
+    List {
+        #[command(subcommand)]
+        what: ListTarget,
+    },
+    #[command(about = "Show everything stored about one issue or contributor")]
+    Show {
+        #[command(subcommand)]
+        what: ShowTarget,
+    },
+    #[command(about = "Approve the budget of an issue")]
+    Approve {
+        issue: String,
+        #[arg(long)]
+        budget: i32,
+    },
+    #[command(about = "Accept or decline an issue for the campaign")]
+    Review {
+        issue: String,
+        #[arg(value_enum)]
+        decision: ReviewDecision,
+    },
+    #[command(about = "Walk through the issues waiting for review")]
+    Queue,
+    #[command(about = "Write the campaign's issues and pull requests as JSON lines")]
+    Export,
+    #[command(about = "Apply the database migrations")]
+    Migrate,
+    #[command(about = "Manage campaign definitions")]
+    Campaign {
+        #[command(subcommand)]
+        action: CampaignAction,
+    },
+    #[command(about = "Run a local check over the stored data")]
+    Check {
+        #[arg(value_enum)]
+        what: CheckTarget,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SyncTarget {
+    Issues,
+    Prs,
+    Comments,
+    Repos,
+    Contributors, // profiles of the authors, commenters and assignees only known by login
+}
+
+#[derive(Args, Clone, Copy, Debug)]
+pub struct SyncOptions {
+    #[arg(long, help = "Continue the last issues or prs run that didn't finish")]
+    pub resume: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "resume",
+        help = "Fetch the whole campaign instead of what changed since the last run"
+    )]
+    pub full: bool,
+
+    #[arg(
+        long,
+        env = "SYNC_CONCURRENCY",
+        default_value_t = DEFAULT_SYNC_CONCURRENCY,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Windows fetched at the same time, lowered to what the rate limit leaves room for"
+    )]
+    pub concurrency: u16,
+
+    #[arg(
+        long,
+        conflicts_with = "resume",
+        help = "Skip issues or prs synced within the campaign's sync_interval_hours"
+    )]
+    pub if_due: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct OutputArgs {
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Columns to print, comma separated"
+    )]
+    pub columns: Vec<String>,
+
+    #[arg(long, help = "Column to sort by")]
+    pub sort: Option<String>,
+
+    #[arg(long, requires = "sort", help = "Sort in descending order")]
+    pub desc: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ListTarget {
+    Projects {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    Issues {
+        #[command(flatten)]
+        filter: IssueFilter,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    Prs {
+        #[arg(long, help = "Only pull requests of this project url")]
+        project: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    Leaderboard {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ShowTarget {
+    Issue {
+        url: String,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    Comments {
+        url: String,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    Contributor {
+        login: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReviewDecision {
+    Approve,
+    Decline,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CampaignAction {
+    #[command(about = "Write the campaigns of the config file to the database")]
+    Apply,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CheckTarget {
+    StaleClaims,
+    Eligibility,
+}
+
+pub struct Context {
+    pub pool: PgPool,
+    pub global: GlobalArgs,
+}
+
+impl Context {
+    fn progress(&self, message: &str) {
+        if self.global.verbose > 0 {
+            eprintln!("{message}");
+        }
+    }
+
+    fn reviewer(&self) -> anyhow::Result<String> {
+        self.global
+            .reviewer
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .filter(|reviewer| !reviewer.trim().is_empty())
+            .ok_or_else(|| anyhow::anyhow!("no reviewer, pass --reviewer or set REVIEWER"))
+    }
+
+    async fn campaign(&self) -> anyhow::Result<Campaign> {
+        get_campaign(&self.pool, &self.global.campaign)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("unknown campaign {}", self.global.campaign))
+    }
+}
+
+pub async fn run(cli: Cli) -> anyhow::Result<()> {
+    let database_url = cli
+        .global
+        .database_url
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("set DATABASE_URL or pass --database-url"))?;
+    if !cli.global.dry_run {
+        let ctx = Context {
+            pool: PgPool::connect(&database_url).await?,
+            global: cli.global,
+        };
+        return dispatch(&ctx, cli.command).await;
+    }
+
+    // migrations commit on their own, they can't be held back
+    if matches!(cli.command, Command::Migrate) {
+        return Err(anyhow::anyhow!("migrate has no dry run"));
+    }
+
+    let ctx = Context {
+        pool: connect_dry_run(&database_url).await?,
+        global: cli.global,
+    };
+    let tables = written_tables(&cli.command);
+    let before = take_snapshot(&ctx.pool, tables).await?;
+    let result = dispatch(&ctx, cli.command).await;
+    let after = take_snapshot(&ctx.pool, tables).await;
+    rollback(&ctx.pool).await?;
+    result?;
+
+    let diffs = diff_snapshots(&before, &after?);
+    print_diff(&diffs);
+
+    let deleted = diffs.iter().map(|diff| diff.deleted.len()).sum::<usize>();
+    if deleted > 0 {
+        return Err(anyhow::anyhow!("dry run would delete {} rows", deleted));
+    }
+    Ok(())
+}
+
+// what a dry run of `command` compares before and after, read-only commands write nothing
+fn written_tables(command: &Command) -> &'static [&'static str] {
+    match command {
+        Command::Sync { what, .. } => match what {
+            SyncTarget::Issues => &[
+                "projects",
+                "campaign_projects",
+                "issues",
+                "comments",
+                "contributors",
+                "issue_references",
+                "issue_assignment_events",
+                "sync_runs",
+                "sync_run_windows",
+                "sync_watermarks",
+            ],
+            SyncTarget::Prs => &[
+                "projects",
+                "campaign_projects",
+                "issues",
+                "pull_requests",
+                "pull_request_reviews",
+                "pull_request_files",
+                "pull_request_checks",
+                "pull_issue_links",
+                "contributors",
+                "sync_runs",
+                "sync_run_windows",
+                "sync_watermarks",
+            ],
+            SyncTarget::Comments => &["comments", "contributors"],
+            SyncTarget::Repos => &["projects"],
+            SyncTarget::Contributors => &["contributors"],
+        },
+        Command::Approve { .. } | Command::Review { .. } | Command::Queue => &["issues"],
+        Command::Campaign { .. } => &["campaigns", "campaign_rules"],
+        Command::Check { what } => match what {
+            CheckTarget::StaleClaims => &["issues"],
+            CheckTarget::Eligibility => &["eligibility_results"],
+        },
+        Command::List { .. } | Command::Show { .. } | Command::Export | Command::Migrate => &[],
+    }
+}
+
+async fn dispatch(ctx: &Context, command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Sync { what, options } => sync(ctx, what, options).await,
+        Command::List { what } => list(ctx, what).await,
+        Command::Show {
+            what: ShowTarget::Issue { url, output },
+        } => show_issue(ctx, &url, &output).await,
+        Command::Show {
+            what: ShowTarget::Comments { url, output },
+        } => {
+            if !issue_exists(&ctx.pool, &url).await? {
+                return Err(anyhow::anyhow!("unknown issue {}", url));
+            }
+            print_rows(list_comments(&ctx.pool, &url).await?, &output)
+        }
+        Command::Show {
+            what: ShowTarget::Contributor { login },
+        } => show_contributor(ctx, &login).await,
+        Command::Approve { issue, budget } => {
+            approve_project_per_issue(&ctx.pool, &issue, budget, true, &ctx.reviewer()?).await
+        }
+        Command::Review { issue, decision } => match decision {
+            // accepting an issue approves the budget it already has, which has to fit the pool
+            ReviewDecision::Approve => {
+                let budget = get_issue(&ctx.pool, &issue)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("unknown issue {}", issue))?
+                    .budget
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("{} has no budget, use approve --budget", issue)
+                    })?;
+                approve_project_per_issue(&ctx.pool, &issue, budget, true, &ctx.reviewer()?).await
+            }
+            ReviewDecision::Decline => decline_issue(&ctx.pool, &issue, &ctx.reviewer()?).await,
+        },
+        Command::Queue => {
+            let campaign = ctx.campaign().await?;
+            let summary =
+                run_review_queue(&ctx.pool, &campaign.campaign_id, &ctx.reviewer()?).await?;
+            println!(
+                "{} approved, {} declined, {} skipped",
+                summary.approved, summary.declined, summary.skipped
+            );
+            Ok(())
+        }
+        Command::Export => export(ctx).await,
+        Command::Migrate => {
+            sqlx::migrate!("./migrations").run(&ctx.pool).await?;
+            Ok(())
+        }
+        Command::Campaign {
+            action: CampaignAction::Apply,
+        } => {
+            let campaigns = apply_campaign_file(&ctx.pool, &ctx.global.config).await?;
+            for campaign in campaigns {
+                println!(
+                    "- {} ({} to {})",
+                    campaign.campaign_id, campaign.starts_on, campaign.ends_on
+                );
+            }
+            Ok(())
+        }
+        Command::Check { what } => check(ctx, what).await,
+    }
+}
+
+async fn sync(ctx: &Context, what: SyncTarget, options: SyncOptions) -> anyhow::Result<()> {
+    let campaign = ctx.campaign().await?;
+    if options.resume
+        && matches!(
+            what,
+            SyncTarget::Comments | SyncTarget::Repos | SyncTarget::Contributors
+        )
+    {
+        return Err(anyhow::anyhow!("only issues and prs syncs can be resumed"));
+    }
+
+    // meant for a scheduler calling in more often than any campaign wants to be synced
+    if options.if_due {
+        let target = match what {
+            SyncTarget::Issues => Some("issues"),
+            SyncTarget::Prs => Some("prs"),
+            SyncTarget::Comments | SyncTarget::Repos | SyncTarget::Contributors => None,
+        };
+        if let (Some(target), Some(hours)) = (target, campaign.sync_interval_hours) {
+            let last = last_finished_sync_run_at(&ctx.pool, &campaign.campaign_id, target).await?;
+            if let Some(due_at) = last.map(|at| at + Duration::hours(i64::from(hours))) {
+                if due_at > Utc::now() {
+                    ctx.progress(&format!("{target} not due until {due_at}"));
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    match what {
+        SyncTarget::Issues => {
+            let searches = [SyncSearch::OpenIssues, SyncSearch::ClosedIssues];
+            sync_searches(ctx, &campaign, "issues", &searches, options).await?;
+        }
+        SyncTarget::Prs => {
+            let searches = [SyncSearch::PullRequests];
+            sync_searches(ctx, &campaign, "prs", &searches, options).await?;
+        }
+        SyncTarget::Comments => {
+            for issue_id in list_campaign_issue_ids(&ctx.pool, &campaign.campaign_id).await? {
+                let comments = update_comments(&ctx.pool, &issue_id).await?;
+                ctx.progress(&format!("{issue_id}: {} comments", comments.len()));
+            }
+        }
+        SyncTarget::Repos => {
+            let project_ids = list_campaign_project_ids(&ctx.pool, &campaign.campaign_id).await?;
+            ctx.progress(&format!("{} repos", project_ids.len()));
+            let not_found =
+                update_projects_metadata(&ctx.pool, &project_ids, &campaign.opt_in_topic).await?;
+            for project_id in not_found {
+                println!("- [{project_id}] not found on GitHub");
+            }
+        }
+        SyncTarget::Contributors => {
+            let logins = list_unfetched_contributors(&ctx.pool).await?;
+            ctx.progress(&format!("{} contributors", logins.len()));
+            for login in update_contributors(&ctx.pool, &logins).await? {
+                println!("- [{login}] not found on GitHub");
+            }
+        }
+    }
+    Ok(())
+}
+
+// search syncs are recorded as sync_runs with a checkpoint per window, a failed run is
+// continued by `--resume` from the page it stopped at; unless `--full`, a new run only asks for
+// what changed since the watermarks of the last finished runs; windows are fetched concurrently,
+// an item found by two windows is written once
+async fn sync_searches(
+    ctx: &Context,
+    campaign: &Campaign,
+    target: &str,
+    searches: &[SyncSearch],
+    options: SyncOptions,
+) -> anyhow::Result<()> {
+    let resumed = if options.resume {
+        get_resumable_sync_run(&ctx.pool, &campaign.campaign_id, target).await?
+    } else {
+        None
+    };
+
+    let run = match resumed {
+        Some(run) => {
+            ctx.progress(&format!(
+                "resuming run {}, {} of {} windows left",
+                run.run_id,
+                run.pending_windows().count(),
+                run.windows.len()
+            ));
+            set_sync_run_status(&ctx.pool, run.run_id, SyncRunStatus::Running, None).await?;
+            run
+        }
+        None => {
+            if options.resume {
+                ctx.progress("nothing to resume, starting a new run");
+            }
+            let watermarks = if options.full {
+                HashMap::new()
+            } else {
+                list_sync_watermarks(&ctx.pool, &campaign.campaign_id).await?
+            };
+            let windows = plan_windows(campaign, searches, &watermarks);
+            let run_id =
+                create_sync_run(&ctx.pool, &campaign.campaign_id, target, &windows).await?;
+            SyncRun {
+                run_id,
+                campaign_id: campaign.campaign_id.clone(),
+                target: target.to_string(),
+                status: SyncRunStatus::Running,
+                windows,
+            }
+        }
+    };
+
+    let concurrency = window_concurrency(
+        usize::from(options.concurrency),
+        get_rate_limit_remaining().await?,
+    );
+    ctx.progress(&format!("fetching {concurrency} windows at a time"));
+
+    let bots = BotFilter::from_env();
+    let seen = SeenNodes::default();
+    let next_index = run.next_window_index();
+    let result = stream::iter(run.pending_windows().map(Ok))
+        .try_for_each_concurrent(concurrency, |window| {
+            sync_window(ctx, campaign, run.run_id, window, &bots, &seen, &next_index)
+        })
+        .await;
+    // the windows still in flight stop where they are, their checkpoints say how far they got
+    if let Err(e) = result {
+        let error = e.to_string();
+        set_sync_run_status(&ctx.pool, run.run_id, SyncRunStatus::Failed, Some(&error)).await?;
+        return Err(anyhow::anyhow!(
+            "sync run {} failed, `sync --resume {}` continues it: {}",
+            run.run_id,
+            target,
+            error
+        ));
+    }
+
+    finish_sync_run(&ctx.pool, run.run_id).await
+}
+
+// fetches the window page by page from its checkpoint on, each page is stored before the
+// checkpoint moves past it so a resumed run repeats at most one page; the windows it gets split
+// into at the search cap are fetched after it by the same task
+async fn sync_window(
+    ctx: &Context,
+    campaign: &Campaign,
+    run_id: i32,
+    window: &SyncWindow,
+    bots: &BotFilter,
+    seen: &SeenNodes,
+    next_index: &AtomicI32,
+) -> anyhow::Result<()> {
+    let mut pending = vec![window.clone()];
+    while let Some(window) = pending.pop() {
+        let split = sync_window_pages(ctx, campaign, run_id, &window, bots, seen, next_index)
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!("window {} ({}): {}", window.window_index, window.query, e)
+            })?;
+        pending.extend(split.into_iter().rev());
+    }
+    Ok(())
+}
+
+// returns the windows the window was split into, empty once it fetched all of its results
+async fn sync_window_pages(
+    ctx: &Context,
+    campaign: &Campaign,
+    run_id: i32,
+    window: &SyncWindow,
+    bots: &BotFilter,
+    seen: &SeenNodes,
+    next_index: &AtomicI32,
+) -> anyhow::Result<Vec<SyncWindow>> {
+    let query = window.search_query();
+    ctx.progress(&format!("searching {query}"));
+    let mut cursor = window.cursor.clone();
+    let mut pages = window.pages;
+
+    loop {
+        let (next_cursor, updated_at) = match window.search {
+            SyncSearch::OpenIssues => {
+                let (mut issues, next_cursor) =
+                    search_issues_open_page(&query, cursor.as_deref()).await?;
+                let updated_at = issues.iter().map(|issue| issue.updated_at).max();
+                issues.retain(|issue| seen.is_new(window.search, &issue.node_id));
+                store_open_issues(ctx, campaign, &issues, bots).await?;
+                seen.mark_seen(
+                    window.search,
+                    issues.iter().map(|issue| issue.node_id.as_str()),
+                );
+                (next_cursor, updated_at)
+            }
+            SyncSearch::ClosedIssues => {
+                let (mut issues, next_cursor) =
+                    search_issues_closed_page(&query, cursor.as_deref()).await?;
+                let updated_at = issues.iter().map(|issue| issue.updated_at).max();
+                issues.retain(|issue| seen.is_new(window.search, &issue.node_id));
+                store_closed_issues(ctx, &issues, bots).await?;
+                seen.mark_seen(
+                    window.search,
+                    issues.iter().map(|issue| issue.node_id.as_str()),
+                );
+                (next_cursor, updated_at)
+            }
+            SyncSearch::PullRequests => {
+                let (mut pulls, next_cursor) =
+                    overall_search_pull_requests_page(&query, cursor.as_deref()).await?;
+                let updated_at = pulls.iter().map(|pull| pull.updated_at).max();
+                pulls.retain(|pull| seen.is_new(window.search, &pull.node_id));
+                store_pull_requests(ctx, campaign, &pulls, bots).await?;
+                seen.mark_seen(
+                    window.search,
+                    pulls.iter().map(|pull| pull.node_id.as_str()),
+                );
+                (next_cursor, updated_at)
+            }
+        };
+
+        pages += 1;
+        // GitHub returns no page past the cap, the rest of the window is fetched as two narrower
+        // windows instead
+        if next_cursor.is_some() && pages >= SEARCH_PAGE_LIMIT {
+            let windows = window.split(next_index).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "more than {} results created on a single day, narrow the campaign's search_scopes",
+                    SEARCH_PAGE_LIMIT * 100
+                )
+            })?;
+            split_sync_window(&ctx.pool, run_id, window.window_index, &windows).await?;
+            ctx.progress(&format!(
+                "window {} hit the search cap, split into windows {} and {}",
+                window.window_index, windows[0].window_index, windows[1].window_index
+            ));
+            return Ok(windows.to_vec());
+        }
+
+        let finished = next_cursor.is_none();
+        save_sync_checkpoint(
+            &ctx.pool,
+            run_id,
+            window.window_index,
+            next_cursor.as_deref(),
+            finished,
+            updated_at,
+        )
+        .await?;
+        if finished {
+            return Ok(Vec::new());
+        }
+        cursor = next_cursor;
+    }
+}
+
+async fn store_open_issues(
+    ctx: &Context,
+    campaign: &Campaign,
+    issues: &[OuterIssue],
+    bots: &BotFilter,
+) -> anyhow::Result<()> {
+    for issue in issues {
+        if !campaign.accepts_repository(&issue.repository) {
+            continue;
+        }
+        add_issue_checked(
+            &ctx.pool,
+            &campaign.campaign_id,
+            &issue.url,
+            &issue.repository,
+            &issue.title.chars().take(200).collect::<String>(),
+            &issue.body.chars().take(200).collect::<String>(),
+            &issue.repository_avatar,
+        )
+        .await?;
+        // issues of opted-out projects aren't stored
+        if issue_exists(&ctx.pool, &issue.url).await? {
+            let author = match issue.author.as_str() {
+                "" => GHOST_LOGIN,
+                author => author,
+            };
+            set_issue_author(&ctx.pool, &issue.url, author).await?;
+            sync_comments(&ctx.pool, &issue.url, &issue.comments).await?;
+            update_issue_references(&ctx.pool, &issue.url).await?;
+            update_issue_assignments(&ctx.pool, &issue.url, bots).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn store_closed_issues(
+    ctx: &Context,
+    issues: &[ClosedIssue],
+    bots: &BotFilter,
+) -> anyhow::Result<()> {
+    for issue in issues {
+        if !issue_exists(&ctx.pool, &issue.url).await? {
+            continue;
+        }
+        let closing_pull = Some(issue.close_pull_request.as_str()).filter(|pull| !pull.is_empty());
+        close_issue(&ctx.pool, &issue.url, closing_pull).await?;
+        sync_comments(&ctx.pool, &issue.url, &issue.comments).await?;
+        update_issue_references(&ctx.pool, &issue.url).await?;
+        update_issue_assignments(&ctx.pool, &issue.url, bots).await?;
+    }
+    Ok(())
+}
+
+async fn store_pull_requests(
+    ctx: &Context,
+    campaign: &Campaign,
+    pulls: &[OuterPull],
+    bots: &BotFilter,
+) -> anyhow::Result<()> {
+    let mut stored = Vec::new();
+    for pull in pulls {
+        if !campaign.accepts_repository(&pull.repository) {
+            continue;
+        }
+        save_pull_request(&ctx.pool, &campaign.campaign_id, pull, bots).await?;
+        // opted-out projects store no pull_requests, nothing to score
+        if pull_request_exists(&ctx.pool, &pull.url).await? {
+            stored.push(pull.url.clone());
+        }
+    }
+    update_spam_scores(&ctx.pool, &stored).await
+}
+
+// applies the column selection and sort of `output` and writes the rows to stdout
+fn print_rows<R: Row>(mut rows: Vec<R>, output: &OutputArgs) -> anyhow::Result<()> {
+    let columns = select_columns::<R>(&output.columns, output.format)?;
+    if let Some(column) = &output.sort {
+        sort_rows(&mut rows, column, output.desc)?;
+    }
+
+    render(
+        &mut std::io::stdout().lock(),
+        &rows,
+        &columns,
+        output.format,
+    )
+}
+
+async fn list(ctx: &Context, what: ListTarget) -> anyhow::Result<()> {
+    match what {
+        ListTarget::Projects { output } => print_rows(list_projects(&ctx.pool).await?, &output),
+        ListTarget::Issues { filter, output } => {
+            let campaign = ctx.campaign().await?;
+            let issues = list_issues(&ctx.pool, &campaign.campaign_id, &filter).await?;
+            print_rows(issues, &output)
+        }
+        ListTarget::Prs { project, output } => {
+            let campaign = ctx.campaign().await?;
+            let pulls =
+                list_pull_requests(&ctx.pool, &campaign.campaign_id, project.as_deref()).await?;
+            print_rows(pulls, &output)
+        }
+        ListTarget::Leaderboard { output } => print_rows(
+            list_leaderboard(&ctx.pool, &BotFilter::from_env()).await?,
+            &output,
+        ),
+    }
+}
+
+async fn show_issue(ctx: &Context, issue_id: &str, output: &OutputArgs) -> anyhow::Result<()> {
+    let issue = get_issue(&ctx.pool, issue_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("unknown issue {}", issue_id))?;
+    print_rows(vec![issue], output)?;
+
+    // the related items are for a reader, jsonl and csv get the issue row alone
+    if output.format != OutputFormat::Table {
+        return Ok(());
+    }
+
+    // who held the issue when a pull request was opened is who the pull request is credited to
+    let bots = BotFilter::from_env();
+    println!("pull requests:");
+    for link in list_issue_links(&ctx.pool, issue_id).await? {
+        let held_by =
+            match assignees_when_pull_opened(&ctx.pool, issue_id, &link.pull_id, &bots).await? {
+                Some(assignees) if !assignees.is_empty() => {
+                    format!(", opened while assigned to {}", assignees.join(", "))
+                }
+                _ => String::new(),
+            };
+        println!("  - [{}] {}{held_by}", link.pull_id, link.origin);
+    }
+    println!("references:");
+    for (source_id, source_type, _, will_close) in
+        list_issue_references(&ctx.pool, issue_id).await?
+    {
+        let closes = if will_close { ", closes" } else { "" };
+        println!("  - [{source_id}] {source_type}{closes}");
+    }
+    println!("assignments:");
+    for event in list_assignment_events(&ctx.pool, issue_id).await? {
+        println!(
+            "  - {} {} by {} on {}",
+            event.event,
+            event.assignee,
+            event.actor,
+            event.created_at.format("%Y-%m-%d")
+        );
+    }
+    println!(
+        "comments: {}",
+        list_comments(&ctx.pool, issue_id).await?.len()
+    );
+    Ok(())
+}
+
+async fn show_contributor(ctx: &Context, login: &str) -> anyhow::Result<()> {
+    let activity = get_contributor_activity(&ctx.pool, login)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("unknown contributor {}", login))?;
+    let contributor = &activity.contributor;
+
+    println!(
+        "{} ({})",
+        contributor.login,
+        contributor.name.as_deref().unwrap_or("profile not fetched")
+    );
+    if let Some(created_at) = contributor.account_created_at {
+        println!("account created: {}", created_at.format("%Y-%m-%d"));
+    }
+    let lists = [
+        ("issues authored", &activity.issues_authored),
+        ("issues assigned", &activity.issues_assigned),
+        ("pull requests", &activity.pulls_authored),
+        ("merged", &activity.pulls_merged),
+    ];
+    for (title, ids) in lists {
+        println!("{title}: {}", ids.len());
+        for id in ids {
+            println!("  - [{id}]");
+        }
+    }
+    println!("comments: {}", activity.comment_count);
+    println!("payouts:");
+    for (issue_id, budget) in &activity.payouts {
+        println!("  - [{issue_id}] {budget}");
+    }
+    Ok(())
+}
+
+async fn export(ctx: &Context) -> anyhow::Result<()> {
+    let campaign = ctx.campaign().await?;
+
+    let issues = export_issues(&ctx.pool, &campaign.campaign_id).await?;
+    let pulls = export_pull_requests(&ctx.pool, &campaign.campaign_id).await?;
+    ctx.progress(&format!(
+        "{} issues, {} pull requests",
+        issues.len(),
+        pulls.len()
+    ));
+
+    for row in issues.iter().chain(pulls.iter()) {
+        println!("{row}");
+    }
+    Ok(())
+}
+
+async fn check(ctx: &Context, what: CheckTarget) -> anyhow::Result<()> {
+    match what {
+        CheckTarget::StaleClaims => {
+            let window_days = std::env::var("STALE_CLAIM_DAYS")
+                .ok()
+                .and_then(|days| days.parse::<i64>().ok())
+                .unwrap_or(DEFAULT_STALE_DAYS);
+
+            for claim in run_stale_claim_check(&ctx.pool, window_days).await? {
+                println!(
+                    "- [{}] {} idle for {} days (assigned {})",
+                    claim.issue_id,
+                    claim.assignee,
+                    claim.days_idle,
+                    claim.assigned_at.format("%Y-%m-%d")
+                );
+            }
+        }
+        // rules come from the json file at ELIGIBILITY_RULES, else from the campaign's file as
+        // applied, else the campaign's defaults; only the campaign's items are checked
+        CheckTarget::Eligibility => {
+            let campaign = ctx.campaign().await?;
+            let rules = match std::env::var("ELIGIBILITY_RULES") {
+                Ok(path) => parse_rules(&std::fs::read_to_string(path)?)?,
+                Err(_) => {
+                    let rules = list_campaign_rules(&ctx.pool, &campaign.campaign_id).await?;
+                    if rules.is_empty() {
+                        default_rules(&campaign)
+                    } else {
+                        rules
+                    }
+                }
+            };
+
+            let results = run_eligibility_rules(
+                &ctx.pool,
+                &campaign.campaign_id,
+                &rules,
+                &BotFilter::from_env(),
+            )
+            .await?;
+            for result in results.iter().filter(|result| !result.passed) {
+                println!(
+                    "- [{}] fails {}: {}",
+                    result.item_id,
+                    result.rule_name,
+                    result.failed.join("; ")
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+
+use crate::bots::BOT_SUFFIX;
+use crate::repository_metadata::{escape, post_aliased_query, BatchLookup};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+// GitHub puts this login on everything authored by a deleted account
+pub const GHOST_LOGIN: &str = "ghost";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Contributor {
+    pub login: String,
+    pub database_id: Option<i64>,
+    pub name: Option<String>,
+    pub company: Option<String>,
+    pub location: Option<String>,
+    pub followers: Option<i64>, // None for organisations
+    pub account_created_at: Option<DateTime<Utc>>,
+    pub avatar_url: Option<String>,
+    pub is_bot: bool,
+    pub is_organization: bool,
+}
+
+impl Contributor {
+    // a row that only holds the login, for authors whose profile wasn't fetched yet
+    pub fn stub(login: &str) -> Self {
+        Contributor {
+            login: login.to_string(),
+            database_id: None,
+            name: None,
+            company: None,
+            location: None,
+            followers: None,
+            account_created_at: None,
+            avatar_url: None,
+            is_bot: login.ends_with(BOT_SUFFIX),
+            is_organization: false,
+        }
+    }
+}
+
+// profiles of many logins in aliased batches
+pub async fn get_contributors(
+    logins: &[String],
+    batch_size: usize,
+) -> anyhow::Result<BatchLookup<Contributor>> {
+    #[derive(Serialize, Deserialize)]
+    struct Owner {
+        __typename: String,
+        login: String,
+        databaseId: Option<i64>,
+        name: Option<String>,
+        company: Option<String>,
+        location: Option<String>,
+        followers: Option<Followers>,
+        createdAt: Option<DateTime<Utc>>,
+        avatarUrl: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Followers {
+        totalCount: i64,
+    }
+
+    let mut lookup = BatchLookup {
+        found: Vec::new(),
+        not_found: Vec::new(),
+    };
+
+    // "[bot]" logins are apps, looking them up would hit the unrelated user of the same name
+    let (bots, users): (Vec<&String>, Vec<&String>) =
+        logins.iter().partition(|login| login.ends_with(BOT_SUFFIX));
+    for login in bots {
+        lookup.found.push((login.clone(), Contributor::stub(login)));
+    }
+
+    for batch in users.chunks(batch_size.max(1)) {
+        let selections = batch
+            .iter()
+            .enumerate()
+            .map(|(i, login)| {
+                format!(
+                    r#"r{}: repositoryOwner(login: "{}") {{ ...ownerFields }}"#,
+                    i,
+                    escape(login)
+                )
+            })
+            .collect::<Vec<String>>();
+
+        let fragments = r#"
+            fragment ownerFields on RepositoryOwner {
+                __typename
+                login
+                avatarUrl
+                ... on User {
+                    databaseId
+                    name
+                    company
+                    location
+                    followers {
+                        totalCount
+                    }
+                    createdAt
+                }
+                ... on Organization {
+                    databaseId
+                    name
+                    location
+                    createdAt
+                }
+            }
+        "#;
+
+        let mut data = post_aliased_query(&selections, fragments).await?;
+
+        for (i, login) in batch.iter().enumerate() {
+            match data.remove(&format!("r{i}")) {
+                Some(value) if !value.is_null() => {
+                    let owner: Owner = serde_json::from_value(value)
+                        .map_err(|e| anyhow!("Failed to deserialize {}: {}", login, e))?;
+                    lookup.found.push((
+                        login.to_string(),
+                        Contributor {
+                            login: login.to_string(), // keeps the casing the other tables use
+                            database_id: owner.databaseId,
+                            name: owner.name,
+                            company: owner.company,
+                            location: owner.location,
+                            followers: owner.followers.map(|followers| followers.totalCount),
+                            account_created_at: owner.createdAt,
+                            avatar_url: owner.avatarUrl,
+                            is_bot: false,
+                            is_organization: owner.__typename == "Organization",
+                        },
+                    ));
+                }
+                _ => lookup.not_found.push(login.to_string()),
+            }
+        }
+    }
+
+    Ok(lookup)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContributorActivity {
+    pub contributor: Contributor,
+    pub issues_authored: Vec<String>,
+    pub issues_assigned: Vec<String>,
+    pub pulls_authored: Vec<String>,
+    pub pulls_merged: Vec<String>, // authored ones that got merged
+    pub comment_count: i64,
+    pub payouts: Vec<(String, i32)>, // approved budgets of the issues assigned to them
+}
@@ -1,4 +1,17 @@
+use crate::bots::BotFilter;
+use crate::contributors::get_contributors;
 use crate::db_updater_local::*;
+use crate::issue_assignments::get_issue_assignment_events;
+use crate::issue_references::get_issue_cross_references;
+use crate::issue_review::ReviewStatus;
+use crate::issues_tracker_local::get_issue_comments;
+use crate::listing::CommentRow;
+use crate::repository_metadata::{
+    get_repositories_metadata, get_repository_metadata, DEFAULT_BATCH_SIZE,
+};
+use crate::spam_score::{
+    is_whitespace_only_diff, may_be_whitespace_only, score_pull_request, SpamScore,
+};
 use sqlx::postgres::PgPool;
 
 pub async fn approve_project_per_issue(
@@ -6,23 +19,84 @@ pub async fn approve_project_per_issue(
     issue_id: &str,
     issue_budget: i32,
     issue_budget_approved: bool, // Assuming this is the correct type for your "approved" column
+    reviewer: &str,
 ) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
+
+    // approved budgets of a campaign can't add up to more than its pool, if it has one. The
+    // campaign row stays locked until the approval commits, so two approvals can't both fit
+    // the same remaining budget
+    if issue_budget_approved {
+        let campaign_id = sqlx::query!(
+            r#"
+            SELECT c.campaign_id
+            FROM issues i
+            JOIN campaigns c ON c.campaign_id = i.campaign_id
+            WHERE i.issue_id = $1
+            FOR UPDATE OF c
+            "#,
+            issue_id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .map(|rec| rec.campaign_id);
+
+        if let Some(campaign_id) = campaign_id {
+            let remaining = campaign_budget_remaining(&mut tx, &campaign_id, issue_id).await?;
+            if let Some(remaining) = remaining.filter(|r| i64::from(issue_budget) > *r) {
+                return Err(anyhow::anyhow!(
+                    "budget {} for {} exceeds the {} left in {}",
+                    issue_budget,
+                    issue_id,
+                    remaining,
+                    campaign_id
+                ));
+            }
+        }
+    }
+
+    // an approved budget approves the issue, taking the approval back leaves the review alone
     let rec = sqlx::query!(
         r#"
         UPDATE issues
-        SET issue_budget = $2, issue_budget_approved = $3
+        SET issue_budget = $2, issue_budget_approved = $3,
+            review_status = CASE WHEN $3 THEN 'approve'::review_status ELSE review_status END,
+            reviewed_by = $4, reviewed_at = NOW()
         WHERE issue_id = $1
         "#,
         issue_id,
         issue_budget,
-        issue_budget_approved
+        issue_budget_approved,
+        reviewer
+    )
+    .execute(&mut tx)
+    .await?;
+
+    if rec.rows_affected() == 0 {
+        return Err(anyhow::anyhow!("unknown issue {}", issue_id));
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// a declined issue gives its budget back to the campaign pool
+pub async fn decline_issue(pool: &PgPool, issue_id: &str, reviewer: &str) -> anyhow::Result<()> {
+    let rec = sqlx::query!(
+        r#"
+        UPDATE issues
+        SET review_status = 'decline', issue_budget_approved = FALSE,
+            reviewed_by = $2, reviewed_at = NOW()
+        WHERE issue_id = $1
+        "#,
+        issue_id,
+        reviewer
     )
     .execute(pool)
     .await?;
 
     if rec.rows_affected() == 0 {
-        // Handle the case where no rows were updated, which could be due to a non-existent issue_id
-        // This is optional and depends on your application's requirements
+        return Err(anyhow::anyhow!("unknown issue {}", issue_id));
     }
 
     Ok(())
@@ -34,7 +108,7 @@ pub async fn pr_pulled_per_issue(
     issue_assignee: &str,
     issue_linked_pr: &str,
     issue_status: &str,
-    review_status: &str, // Ensure this matches your database schema type and Rust's type handling
+    review_status: ReviewStatus,
 ) -> anyhow::Result<()> {
     let rec = sqlx::query!(
         r#"
@@ -49,36 +123,234 @@ pub async fn pr_pulled_per_issue(
         issue_assignee,
         issue_linked_pr,
         issue_status,
-        review_status
+        review_status as ReviewStatus
     )
     .execute(pool)
     .await?;
 
     if rec.rows_affected() == 0 {
-        // Handle the case where no rows were updated, which could be due to a non-existent issue_id
-        // This is optional and depends on your application's requirements
+        return Err(anyhow::anyhow!("unknown issue {}", issue_id));
     }
 
     Ok(())
 }
 
-//
-pub async fn update_comments(pool: &PgPool, issue_id: &str) -> anyhow::Result<()> {
-    let recs = sqlx::query!(
-        r#"
-        SELECT comment_id, content
-        FROM comments
-        WHERE issue_id = $1
-        ORDER BY comment_id
-        "#,
-        issue_id
-    )
-    .fetch_all(pool)
-    .await?;
+// pulls every comment of the issue from GitHub, edited comments are refreshed and the ones
+// no longer on GitHub are marked deleted; returns the comments stored afterwards
+pub async fn update_comments(pool: &PgPool, issue_id: &str) -> anyhow::Result<Vec<CommentRow>> {
+    let comments = get_issue_comments(issue_id).await?;
+
+    sync_comments(pool, issue_id, &comments).await?;
+
+    list_comments(pool, issue_id).await
+}
+
+// stores the pull_requests, issues and commits that mention the issue, a merged pull_request
+// from another repo that closes it becomes its linked pr
+pub async fn update_issue_references(pool: &PgPool, issue_id: &str) -> anyhow::Result<()> {
+    let references = get_issue_cross_references(issue_id).await?;
+
+    sync_issue_references(pool, issue_id, &references).await
+}
+
+// records the assignment history of the issue and derives issue_assignee from it
+pub async fn update_issue_assignments(
+    pool: &PgPool,
+    issue_id: &str,
+    bots: &BotFilter,
+) -> anyhow::Result<()> {
+    let events = get_issue_assignment_events(issue_id).await?;
+
+    sync_assignment_events(pool, &events).await?;
+    refresh_issue_assignee(pool, issue_id, bots).await
+}
+
+// "https://github.com/owner/repo" -> ("owner", "repo")
+fn split_project_id(project_id: &str) -> anyhow::Result<(&str, &str)> {
+    let mut parts = project_id
+        .trim_end_matches('/')
+        .rsplit('/')
+        .take(2)
+        .collect::<Vec<&str>>();
+    parts.reverse();
+    match parts.as_slice() {
+        [owner, repo] => Ok((*owner, *repo)),
+        _ => Err(anyhow::anyhow!("not a repository url: {}", project_id)),
+    }
+}
+
+// refreshes the repo metadata of a project and records whether it follows the campaign opt-in
+// rule, returns the verdict
+pub async fn update_project_metadata(
+    pool: &PgPool,
+    project_id: &str,
+    opt_in_topic: &str,
+) -> anyhow::Result<bool> {
+    let (owner, repo) = split_project_id(project_id)?;
+    let metadata = get_repository_metadata(owner, repo).await?;
+    let opted_in = metadata.is_opted_in(opt_in_topic);
+
+    save_project_metadata(pool, &metadata, opted_in).await?;
+
+    Ok(opted_in)
+}
+
+// same as update_project_metadata for many projects, DEFAULT_BATCH_SIZE repos per request;
+// returns the projects GitHub could not find
+pub async fn update_projects_metadata(
+    pool: &PgPool,
+    project_ids: &[String],
+    opt_in_topic: &str,
+) -> anyhow::Result<Vec<String>> {
+    let repos = project_ids
+        .iter()
+        .map(|project_id| split_project_id(project_id))
+        .collect::<anyhow::Result<Vec<(&str, &str)>>>()?;
+
+    let lookup = get_repositories_metadata(&repos, DEFAULT_BATCH_SIZE).await?;
+
+    for (_, metadata) in &lookup.found {
+        save_project_metadata(pool, metadata, metadata.is_opted_in(opt_in_topic)).await?;
+    }
+
+    Ok(lookup.not_found)
+}
+
+// fetches the GitHub profile of each login, DEFAULT_BATCH_SIZE per request; logins GitHub no
+// longer knows are left as stubs and returned
+pub async fn update_contributors(pool: &PgPool, logins: &[String]) -> anyhow::Result<Vec<String>> {
+    let lookup = get_contributors(logins, DEFAULT_BATCH_SIZE).await?;
+
+    for (_, contributor) in &lookup.found {
+        upsert_contributor(pool, contributor).await?;
+    }
+
+    Ok(lookup.not_found)
+}
+
+// scores stored pull_requests, the profiles of authors whose account age is still unknown are
+// fetched once for the whole batch first
+pub async fn update_spam_scores(pool: &PgPool, pull_ids: &[String]) -> anyhow::Result<()> {
+    let authors = list_unprofiled_authors(pool, pull_ids).await?;
+    if !authors.is_empty() {
+        update_contributors(pool, &authors).await?;
+    }
 
-    for rec in recs {
-        println!("- [{}] {}", rec.comment_id, rec.content);
+    for pull_id in pull_ids {
+        update_spam_score(pool, pull_id).await?;
     }
 
     Ok(())
 }
+
+// the diff is only downloaded when the stored file stats leave whitespace-only possible
+pub async fn update_spam_score(pool: &PgPool, pull_id: &str) -> anyhow::Result<SpamScore> {
+    let mut signals = get_spam_signals(pool, pull_id).await?;
+    if may_be_whitespace_only(&signals.files) {
+        signals.whitespace_only = is_whitespace_only_diff(pull_id).await?;
+    }
+    let score = score_pull_request(&signals);
+
+    save_spam_score(pool, pull_id, &score).await?;
+
+    Ok(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::campaigns::DEFAULT_CAMPAIGN_ID;
+
+    async fn seed_issue(pool: &PgPool, issue_id: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO issues (issue_id, project_id, issue_title, issue_description, campaign_id)
+            VALUES ($1, 'https://github.com/owner/repo', 'title', 'body', $2)
+            "#,
+        )
+        .bind(issue_id)
+        .bind(DEFAULT_CAMPAIGN_ID)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    const FIRST_ISSUE: &str = "https://github.com/owner/repo/issues/1";
+    const SECOND_ISSUE: &str = "https://github.com/owner/repo/issues/2";
+
+    #[sqlx::test]
+    async fn an_approval_waits_for_one_in_flight(pool: PgPool) -> anyhow::Result<()> {
+        sqlx::query("UPDATE campaigns SET budget_pool = 100 WHERE campaign_id = $1")
+            .bind(DEFAULT_CAMPAIGN_ID)
+            .execute(&pool)
+            .await?;
+        seed_issue(&pool, FIRST_ISSUE).await?;
+        seed_issue(&pool, SECOND_ISSUE).await?;
+
+        // an approval of the first issue that has passed its check but not committed yet
+        let mut in_flight = pool.begin().await?;
+        sqlx::query("SELECT campaign_id FROM campaigns WHERE campaign_id = $1 FOR UPDATE")
+            .bind(DEFAULT_CAMPAIGN_ID)
+            .execute(&mut in_flight)
+            .await?;
+        sqlx::query(
+            "UPDATE issues SET issue_budget = 60, issue_budget_approved = TRUE WHERE issue_id = $1",
+        )
+        .bind(FIRST_ISSUE)
+        .execute(&mut in_flight)
+        .await?;
+
+        let second_pool = pool.clone();
+        let second = tokio::spawn(async move {
+            approve_project_per_issue(&second_pool, SECOND_ISSUE, 60, true, "bob").await
+        });
+        // let the second approval run until it is done or blocked on the campaign row
+        while !second.is_finished() {
+            let waiting: i64 = sqlx::query_scalar(
+                r#"
+                SELECT COUNT(*)
+                FROM pg_stat_activity
+                WHERE datname = current_database() AND wait_event_type = 'Lock'
+                "#,
+            )
+            .fetch_one(&pool)
+            .await?;
+            if waiting > 0 {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        in_flight.commit().await?;
+
+        assert!(second.await?.is_err());
+        let approved: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(issue_budget), 0) FROM issues WHERE issue_budget_approved",
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(approved, 60);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn a_rejected_approval_leaves_the_issue_alone(pool: PgPool) -> anyhow::Result<()> {
+        sqlx::query("UPDATE campaigns SET budget_pool = 10 WHERE campaign_id = $1")
+            .bind(DEFAULT_CAMPAIGN_ID)
+            .execute(&pool)
+            .await?;
+        seed_issue(&pool, FIRST_ISSUE).await?;
+
+        assert!(
+            approve_project_per_issue(&pool, FIRST_ISSUE, 20, true, "alice")
+                .await
+                .is_err()
+        );
+        let approved: Option<bool> =
+            sqlx::query_scalar("SELECT issue_budget_approved FROM issues WHERE issue_id = $1")
+                .bind(FIRST_ISSUE)
+                .fetch_one(&pool)
+                .await?;
+        assert_ne!(approved, Some(true));
+        Ok(())
+    }
+}
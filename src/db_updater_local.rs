@@ -1,4 +1,27 @@
-/* use sqlx::postgres::PgPool;
+use chrono::{DateTime, Utc};
+
+use crate::bots::BotFilter;
+use crate::campaigns::{Campaign, DEFAULT_CAMPAIGN_ID};
+use crate::contributors::{Contributor, ContributorActivity};
+use crate::eligibility::{EligibilityRule, ItemKind};
+use crate::issue_assignments::{assignees_at, current_assignees, AssignmentEvent};
+use crate::issue_links::{LinkOrigin, PullIssueLink};
+use crate::issue_references::IssueReference;
+use crate::issue_review::ReviewStatus;
+use crate::issues_tracker_local::IssueComment;
+use crate::listing::{
+    CommentRow, IssueFilter, IssueRow, LeaderboardRow, ProjectRow, PullRequestRow,
+};
+use crate::pull_checks::{PullCheck, PullChecks};
+use crate::pull_files::PullFile;
+use crate::pull_request_overall_search::OuterPull;
+use crate::pull_reviews::PullReview;
+use crate::repository_metadata::RepoMetadata;
+use crate::spam_score::{SpamScore, SpamSignals, BURST_WINDOW_HOURS};
+use crate::stale_claims::clear_stale_claims;
+use crate::sync_runs::{SyncRun, SyncRunStatus, SyncSearch, SyncWindow};
+use sqlx::postgres::{PgConnection, PgPool};
+use std::collections::HashMap;
 
 pub async fn project_exists(pool: &PgPool, project_id: &str) -> anyhow::Result<bool> {
     let exists = sqlx::query!(
@@ -48,12 +71,70 @@ pub async fn add_project_checked(
     Ok(())
 }
 
+pub async fn save_project_metadata(
+    pool: &PgPool,
+    metadata: &RepoMetadata,
+    opted_in: bool,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO projects (project_id, project_logo, issues_list, description, primary_language, languages, topics, license, stars, is_archived, is_fork, default_branch, opted_in)
+        VALUES ($1, $2, ARRAY[]::text[], $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        ON CONFLICT (project_id) DO UPDATE
+        SET project_logo = EXCLUDED.project_logo,
+            description = EXCLUDED.description,
+            primary_language = EXCLUDED.primary_language,
+            languages = EXCLUDED.languages,
+            topics = EXCLUDED.topics,
+            license = EXCLUDED.license,
+            stars = EXCLUDED.stars,
+            is_archived = EXCLUDED.is_archived,
+            is_fork = EXCLUDED.is_fork,
+            default_branch = EXCLUDED.default_branch,
+            opted_in = EXCLUDED.opted_in
+        "#,
+        metadata.project_id,
+        metadata.project_logo,
+        metadata.description,
+        metadata.primary_language,
+        &metadata.languages,
+        &metadata.topics,
+        metadata.license,
+        metadata.stars,
+        metadata.is_archived,
+        metadata.is_fork,
+        metadata.default_branch,
+        opted_in,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// only a verified opt-out excludes a project, repos we haven't checked yet are let through
+pub async fn project_opted_out(pool: &PgPool, project_id: &str) -> anyhow::Result<bool> {
+    let opted_out = sqlx::query!(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM projects WHERE project_id = $1 AND opted_in = FALSE) AS "exists!"
+        "#,
+        project_id
+    )
+    .fetch_one(pool)
+    .await?
+    .exists;
+
+    Ok(opted_out)
+}
+
+// issues are synced again and again, an issue already on the list isn't added twice
 pub async fn update_project(pool: &PgPool, project_id: &str, issue_id: &str) -> anyhow::Result<()> {
     sqlx::query!(
         r#"
         UPDATE projects
         SET issues_list = array_append(issues_list, $1)
         WHERE project_id = $2
+            AND NOT ($1 = ANY(COALESCE(issues_list, '{}')))
         "#,
         issue_id,
         project_id,
@@ -69,16 +150,17 @@ pub async fn add_project_test_1(pool: &PgPool) -> anyhow::Result<()> {
     let issue_id = "https://github.com/jaykchen/issue-labeler/issues/24";
     let project_logo = "https://avatars.githubusercontent.com/u/112579101?v=4";
 
-    let _ = add_project(pool, project_id, project_logo, issue_id).await?;
+    add_project(pool, project_id, project_logo, issue_id).await?;
 
     Ok(())
 }
 
-pub async fn list_projects(pool: &PgPool) -> anyhow::Result<Vec<(String, String, Vec<String>)>> {
+pub async fn list_projects(pool: &PgPool) -> anyhow::Result<Vec<ProjectRow>> {
     let recs = sqlx::query!(
         r#"
-        SELECT project_id, project_logo, issues_list
+        SELECT project_id, project_logo, issues_list, opted_in
         FROM projects
+        WHERE opted_in IS NOT FALSE
         ORDER BY project_id
         "#
     )
@@ -86,18 +168,18 @@ pub async fn list_projects(pool: &PgPool) -> anyhow::Result<Vec<(String, String,
     .await?;
 
     let projects = recs
-        .iter()
-        .map(|r| {
-            (
-                r.project_id.clone(),
-                r.project_logo.clone(),
-                r.issues_list.clone().unwrap_or_default(), // Handle Option<Vec<String>>
-            )
+        .into_iter()
+        .map(|r| ProjectRow {
+            project_id: r.project_id,
+            project_logo: r.project_logo,
+            issue_count: r.issues_list.map_or(0, |issues| issues.len() as i64),
+            opted_in: r.opted_in,
         })
         .collect();
 
     Ok(projects)
 }
+
 pub async fn issue_exists(pool: &PgPool, issue_id: &str) -> anyhow::Result<bool> {
     let exists = sqlx::query!(
         r#"
@@ -114,27 +196,58 @@ pub async fn issue_exists(pool: &PgPool, issue_id: &str) -> anyhow::Result<bool>
 
 pub async fn add_issue_checked(
     pool: &PgPool,
+    campaign_id: &str,
     issue_id: &str,
     project_id: &str,
     title: &str,
     description: &str,
     repository_avatar: &str,
 ) -> anyhow::Result<()> {
+    if project_opted_out(pool, project_id).await? {
+        return Ok(());
+    }
+
     if project_exists(pool, project_id).await? {
         update_project(pool, project_id, issue_id).await?;
     } else {
         add_project(pool, project_id, repository_avatar, issue_id).await?;
     }
+    add_campaign_project(pool, campaign_id, project_id).await?;
 
     if issue_exists(pool, issue_id).await? {
+        update_issue_text(pool, issue_id, title, description).await?;
     } else {
-        add_issue(pool, issue_id, project_id, title, description).await?;
+        add_issue(pool, campaign_id, issue_id, project_id, title, description).await?;
     }
     Ok(())
 }
 
+// title and body as edited on GitHub since the last sync
+pub async fn update_issue_text(
+    pool: &PgPool,
+    issue_id: &str,
+    title: &str,
+    description: &str,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE issues
+        SET issue_title = $2, issue_description = $3
+        WHERE issue_id = $1
+            AND (issue_title <> $2 OR issue_description <> $3)
+        "#,
+        issue_id,
+        title,
+        description,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 pub async fn add_issue(
     pool: &PgPool,
+    campaign_id: &str,
     issue_id: &str,
     project_id: &str,
     title: &str,
@@ -142,13 +255,14 @@ pub async fn add_issue(
 ) -> anyhow::Result<()> {
     sqlx::query!(
         r#"
-        INSERT INTO issues (issue_id, project_id, issue_title, issue_description)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO issues (issue_id, project_id, issue_title, issue_description, campaign_id)
+        VALUES ($1, $2, $3, $4, $5)
         "#,
         issue_id,
         project_id,
         title,
         description,
+        campaign_id,
     )
     .execute(pool)
     .await?;
@@ -161,183 +275,563 @@ pub async fn add_issue_test_1(pool: &PgPool) -> anyhow::Result<()> {
     let title = "WASI-NN with GPU on Jetson Orin Nano";
     let description = "demo";
 
-    let _ = add_issue(pool, issue_id, project_id, title, description).await?;
+    add_issue(
+        pool,
+        DEFAULT_CAMPAIGN_ID,
+        issue_id,
+        project_id,
+        title,
+        description,
+    )
+    .await?;
     Ok(())
 }
 
-pub async fn list_issues(pool: &PgPool, project_id: &str) -> anyhow::Result<()> {
+// effort adds up the merged pull_requests linked to the issue, mentions are left out
+pub async fn list_issues(
+    pool: &PgPool,
+    campaign_id: &str,
+    filter: &IssueFilter,
+) -> anyhow::Result<Vec<IssueRow>> {
     let recs = sqlx::query!(
         r#"
-        SELECT issue_id, issue_title, issue_description, issue_budget
-        FROM issues
-        WHERE project_id = $1
-        ORDER BY issue_id
+        SELECT i.issue_id, i.project_id, i.issue_title, i.issue_description, i.issue_author,
+            i.issue_assignee, i.issue_budget, i.issue_budget_approved, i.issue_status,
+            i.review_status AS "review_status: ReviewStatus", i.issue_linked_pr,
+            COUNT(p.pull_id) AS "pulls!",
+            COALESCE(SUM(p.additions), 0) AS "additions!",
+            COALESCE(SUM(p.deletions), 0) AS "deletions!",
+            COALESCE(SUM(p.changed_files), 0) AS "changed_files!",
+            COALESCE(SUM(p.commit_count), 0) AS "commits!"
+        FROM issues i
+        LEFT JOIN (
+            SELECT DISTINCT pull_id, issue_id
+            FROM pull_issue_links
+            WHERE origin <> 'mention'
+        ) l ON l.issue_id = i.issue_id
+        LEFT JOIN pull_requests p ON p.pull_id = l.pull_id AND p.merged_at IS NOT NULL
+        WHERE i.campaign_id = $1
+            AND ($2::VARCHAR IS NULL OR i.issue_status = $2)
+            AND ($3::review_status IS NULL OR i.review_status = $3)
+            AND ($4::INT IS NULL OR i.issue_budget >= $4)
+            AND ($5::INT IS NULL OR i.issue_budget <= $5)
+            AND ($6::VARCHAR IS NULL OR i.issue_assignee = $6)
+            AND ($7::VARCHAR IS NULL OR i.project_id = $7)
+        GROUP BY i.issue_id
+        ORDER BY i.issue_id
         "#,
-        project_id
+        campaign_id,
+        filter.status,
+        filter.review_status as Option<ReviewStatus>,
+        filter.min_budget,
+        filter.max_budget,
+        filter.assignee,
+        filter
+            .project
+            .as_deref()
+            .map(|project| project.trim_end_matches('/')),
     )
     .fetch_all(pool)
     .await?;
 
-    for rec in recs {
-        println!(
-            "- [{}] {}: {} (${:?})",
-            rec.issue_id, rec.issue_title, rec.issue_description, rec.issue_budget
-        );
+    Ok(recs
+        .into_iter()
+        .map(|r| IssueRow {
+            issue_id: r.issue_id,
+            project_id: r.project_id,
+            title: r.issue_title,
+            description: r.issue_description,
+            author: r.issue_author,
+            assignee: r.issue_assignee,
+            budget: r.issue_budget,
+            budget_approved: r.issue_budget_approved,
+            status: r.issue_status,
+            review_status: r.review_status,
+            linked_pr: r.issue_linked_pr,
+            pulls: r.pulls,
+            additions: r.additions,
+            deletions: r.deletions,
+            changed_files: r.changed_files,
+            commits: r.commits,
+        })
+        .collect())
+}
+
+pub async fn get_issue(pool: &PgPool, issue_id: &str) -> anyhow::Result<Option<IssueRow>> {
+    let rec = sqlx::query!(
+        r#"
+        SELECT i.issue_id, i.project_id, i.issue_title, i.issue_description, i.issue_author,
+            i.issue_assignee, i.issue_budget, i.issue_budget_approved, i.issue_status,
+            i.review_status AS "review_status: ReviewStatus", i.issue_linked_pr,
+            COUNT(p.pull_id) AS "pulls!",
+            COALESCE(SUM(p.additions), 0) AS "additions!",
+            COALESCE(SUM(p.deletions), 0) AS "deletions!",
+            COALESCE(SUM(p.changed_files), 0) AS "changed_files!",
+            COALESCE(SUM(p.commit_count), 0) AS "commits!"
+        FROM issues i
+        LEFT JOIN (
+            SELECT DISTINCT pull_id, issue_id
+            FROM pull_issue_links
+            WHERE origin <> 'mention'
+        ) l ON l.issue_id = i.issue_id
+        LEFT JOIN pull_requests p ON p.pull_id = l.pull_id AND p.merged_at IS NOT NULL
+        WHERE i.issue_id = $1
+        GROUP BY i.issue_id
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(rec.map(|r| IssueRow {
+        issue_id: r.issue_id,
+        project_id: r.project_id,
+        title: r.issue_title,
+        description: r.issue_description,
+        author: r.issue_author,
+        assignee: r.issue_assignee,
+        budget: r.issue_budget,
+        budget_approved: r.issue_budget_approved,
+        status: r.issue_status,
+        review_status: r.review_status,
+        linked_pr: r.issue_linked_pr,
+        pulls: r.pulls,
+        additions: r.additions,
+        deletions: r.deletions,
+        changed_files: r.changed_files,
+        commits: r.commits,
+    }))
+}
+
+pub async fn upsert_comment(
+    pool: &PgPool,
+    issue_id: &str,
+    comment: &IssueComment,
+) -> anyhow::Result<()> {
+    add_contributor_stub(pool, &comment.author).await?;
+
+    // an edited comment keeps its url, so the same row is refreshed on every re-sync
+    sqlx::query!(
+        r#"
+        INSERT INTO comments (comment_id, issue_id, creator, content, database_id, created_at, updated_at, deleted)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, FALSE)
+        ON CONFLICT (comment_id) DO UPDATE
+        SET creator = EXCLUDED.creator,
+            content = EXCLUDED.content,
+            updated_at = EXCLUDED.updated_at,
+            deleted = FALSE
+        "#,
+        comment.comment_id,
+        issue_id,
+        comment.author,
+        comment.body,
+        comment.database_id,
+        comment.created_at,
+        comment.updated_at,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn mark_deleted_comments(
+    pool: &PgPool,
+    issue_id: &str,
+    live_comment_ids: &[String],
+) -> anyhow::Result<u64> {
+    let rec = sqlx::query!(
+        r#"
+        UPDATE comments
+        SET deleted = TRUE
+        WHERE issue_id = $1
+            AND deleted = FALSE
+            AND NOT (comment_id = ANY($2))
+        "#,
+        issue_id,
+        live_comment_ids,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(rec.rows_affected())
+}
+
+// writes the complete comment list of an issue, comments missing from `comments` are
+// flagged as deleted instead of being removed
+pub async fn sync_comments(
+    pool: &PgPool,
+    issue_id: &str,
+    comments: &[IssueComment],
+) -> anyhow::Result<()> {
+    for comment in comments {
+        upsert_comment(pool, issue_id, comment).await?;
     }
 
+    let live_comment_ids = comments
+        .iter()
+        .map(|comment| comment.comment_id.clone())
+        .collect::<Vec<String>>();
+    mark_deleted_comments(pool, issue_id, &live_comment_ids).await?;
+
     Ok(())
 }
 
-pub async fn get_issue(pool: &PgPool, issue_id: &str) -> anyhow::Result<()> {
+pub async fn list_comments(pool: &PgPool, issue_id: &str) -> anyhow::Result<Vec<CommentRow>> {
     let recs = sqlx::query!(
         r#"
-        SELECT issue_id, issue_title, issue_description, issue_budget
-        FROM issues
-        WHERE issue_id = $1
-        ORDER BY issue_id
+        SELECT comment_id, issue_id, creator, created_at, content
+        FROM comments
+        WHERE issue_id = $1 AND deleted = FALSE
+        ORDER BY created_at
         "#,
         issue_id
     )
     .fetch_all(pool)
     .await?;
 
-    for rec in recs {
-        println!(
-            "- [{}] {}: {} (${:?})",
-            rec.issue_id, rec.issue_title, rec.issue_description, rec.issue_budget
-        );
-    }
+    let comments = recs
+        .into_iter()
+        .map(|r| CommentRow {
+            comment_id: r.comment_id,
+            issue_id: r.issue_id,
+            creator: r.creator,
+            created_at: r.created_at,
+            content: r.content,
+        })
+        .collect();
+
+    Ok(comments)
+}
+
+pub async fn add_issue_reference(pool: &PgPool, reference: &IssueReference) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_references (issue_id, source_id, source_type, source_state, source_repository, actor, is_cross_repository, will_close_target, referenced_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (issue_id, source_id) DO UPDATE
+        SET source_state = EXCLUDED.source_state,
+            will_close_target = EXCLUDED.will_close_target
+        "#,
+        reference.issue_id,
+        reference.source_id,
+        reference.source_type,
+        reference.source_state,
+        reference.source_repository,
+        reference.actor,
+        reference.is_cross_repository,
+        reference.will_close_target,
+        reference.referenced_at,
+    )
+    .execute(pool)
+    .await?;
+
+    if reference.source_type == "PullRequest" {
+        sqlx::query!(
+            r#"
+            UPDATE pull_requests
+            SET cross_referenced_issues = array_append(cross_referenced_issues, $1)
+            WHERE pull_id = $2
+                AND NOT ($1 = ANY(COALESCE(cross_referenced_issues, ARRAY[]::text[])))
+            "#,
+            reference.issue_id,
+            reference.source_id,
+        )
+        .execute(pool)
+        .await?;
 
+        add_pull_issue_link(
+            pool,
+            &PullIssueLink {
+                pull_id: reference.source_id.clone(),
+                issue_id: reference.issue_id.clone(),
+                origin: LinkOrigin::CrossReference,
+            },
+        )
+        .await?;
+    }
     Ok(())
 }
 
-/* pub async fn add_comment(
-    pool: &PgPool,
-    comment_id: &str,
-    issue_id: &str,
-    creator: &str,
-    content: &str,
-) -> anyhow::Result<()> {
+// an issue closed by a pull_request in another repo has no closer in its ClosedEvent,
+// fall back to the merged pull_request that declared it would close the issue
+pub async fn attribute_cross_referenced_pull(pool: &PgPool, issue_id: &str) -> anyhow::Result<()> {
     sqlx::query!(
         r#"
-        INSERT INTO comments (comment_id, issue_id, creator, content)
-        VALUES ($1, $2, $3, $4)
+        UPDATE issues
+        SET issue_linked_pr = (
+            SELECT source_id
+            FROM issue_references
+            WHERE issue_id = $1
+                AND source_type = 'PullRequest'
+                AND source_state = 'MERGED'
+                AND will_close_target
+            ORDER BY referenced_at DESC
+            LIMIT 1
+        )
+        WHERE issue_id = $1
+            AND issue_linked_pr IS NULL
+            AND EXISTS (
+                SELECT 1
+                FROM issue_references
+                WHERE issue_id = $1
+                    AND source_type = 'PullRequest'
+                    AND source_state = 'MERGED'
+                    AND will_close_target
+            )
         "#,
-        comment_id,
         issue_id,
-        creator,
-        content,
     )
     .execute(pool)
     .await?;
     Ok(())
 }
-*/
 
-/* pub async fn add_comment_checked(
+pub async fn sync_issue_references(
     pool: &PgPool,
-    comment_id: &str,
     issue_id: &str,
-    creator: &str,
-    content: &str,
+    references: &[IssueReference],
 ) -> anyhow::Result<()> {
-    if issue_exists(pool, issue_id).await? {
-    } else {
-        let _ = add_issue_checked(pool, issue_id, "title", "description").await?;
+    for reference in references {
+        add_issue_reference(pool, reference).await?;
     }
 
+    attribute_cross_referenced_pull(pool, issue_id).await?;
+
+    Ok(())
+}
+
+pub async fn list_issue_references(
+    pool: &PgPool,
+    issue_id: &str,
+) -> anyhow::Result<Vec<(String, String, bool, bool)>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT source_id, source_type, is_cross_repository, will_close_target
+        FROM issue_references
+        WHERE issue_id = $1
+        ORDER BY referenced_at
+        "#,
+        issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let references = recs
+        .iter()
+        .map(|r| {
+            (
+                r.source_id.clone(),
+                r.source_type.clone(),
+                r.is_cross_repository,
+                r.will_close_target,
+            )
+        })
+        .collect();
+
+    Ok(references)
+}
+
+pub async fn add_pull_issue_link(pool: &PgPool, link: &PullIssueLink) -> anyhow::Result<()> {
     sqlx::query!(
         r#"
-            INSERT INTO comments (comment_id, issue_id, creator, content)
-            VALUES ($1, $2, $3, $4)
-            "#,
-        comment_id,
-        issue_id,
-        creator,
-        content
+        INSERT INTO pull_issue_links (pull_id, issue_id, origin)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        link.pull_id,
+        link.issue_id,
+        link.origin as LinkOrigin,
     )
     .execute(pool)
     .await?;
     Ok(())
 }
 
-pub async fn add_comment_test_1(pool: &PgPool) -> anyhow::Result<()> {
-    let comment_id = "https://github.com/jaykchen/issue-labeler/issues/24#issuecomment-1979927212";
-    let issue_id = "https://github.com/jaykchen/issue-labeler/issues/24";
-    let creator = "jaykchen";
-    let content = "This is a placeholder comment on this issue.";
+// the links found on the pull_request itself replace the stored ones, an issue disconnected in
+// the sidebar or edited out of the body loses its link; cross_reference links come from the
+// issue's timeline and are left alone. An issue that lost its only link to the pull_request
+// stops naming it as its linked pr
+pub async fn sync_pull_issue_links(
+    pool: &PgPool,
+    pull_id: &str,
+    links: &[PullIssueLink],
+) -> anyhow::Result<()> {
+    let issue_ids = links
+        .iter()
+        .map(|link| link.issue_id.clone())
+        .collect::<Vec<String>>();
+    let origins = links
+        .iter()
+        .map(|link| link.origin.to_string())
+        .collect::<Vec<String>>();
+
+    let dropped = sqlx::query!(
+        r#"
+        DELETE FROM pull_issue_links l
+        WHERE l.pull_id = $1
+            AND l.origin <> 'cross_reference'
+            AND NOT EXISTS (
+                SELECT 1 FROM unnest($2::TEXT[], $3::TEXT[]) AS kept(issue_id, origin)
+                WHERE kept.issue_id = l.issue_id AND kept.origin = l.origin::TEXT
+            )
+        RETURNING l.issue_id
+        "#,
+        pull_id,
+        &issue_ids,
+        &origins,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|rec| rec.issue_id)
+    .collect::<Vec<String>>();
+
+    for link in links {
+        add_pull_issue_link(pool, link).await?;
+    }
+
     sqlx::query!(
         r#"
-        INSERT INTO comments (comment_id, issue_id, creator, content)
-        VALUES ($1, $2, $3, $4)
+        UPDATE issues i
+        SET issue_linked_pr = NULL
+        WHERE i.issue_linked_pr = $1
+            AND i.issue_id = ANY($2)
+            AND NOT EXISTS (
+                SELECT 1 FROM pull_issue_links l
+                WHERE l.pull_id = $1 AND l.issue_id = i.issue_id
+            )
         "#,
-        comment_id,
-        issue_id,
-        creator,
-        content
+        pull_id,
+        &dropped,
     )
     .execute(pool)
     .await?;
+
     Ok(())
 }
-*/
-/* pub async fn list_comments(pool: &PgPool, issue_id: &str) -> anyhow::Result<()> {
-    let recs = sqlx::query!(
+
+pub async fn list_issue_links(pool: &PgPool, issue_id: &str) -> anyhow::Result<Vec<PullIssueLink>> {
+    let links = sqlx::query_as!(
+        PullIssueLink,
         r#"
-        SELECT comment_id, content
-        FROM comments
+        SELECT pull_id, issue_id, origin AS "origin: LinkOrigin"
+        FROM pull_issue_links
         WHERE issue_id = $1
-        ORDER BY comment_id
+        ORDER BY pull_id
         "#,
         issue_id
     )
     .fetch_all(pool)
     .await?;
 
-    for rec in recs {
-        println!("- [{}] {}", rec.comment_id, rec.content);
+    Ok(links)
+}
+
+pub async fn upsert_pull_review(pool: &PgPool, review: &PullReview) -> anyhow::Result<()> {
+    // a dismissed approval keeps its url and flips its state to DISMISSED
+    sqlx::query!(
+        r#"
+        INSERT INTO pull_request_reviews (review_id, pull_id, reviewer, state, submitted_at, author_association)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (review_id) DO UPDATE
+        SET state = EXCLUDED.state,
+            submitted_at = EXCLUDED.submitted_at,
+            author_association = EXCLUDED.author_association
+        "#,
+        review.review_id,
+        review.pull_id,
+        review.reviewer,
+        review.state,
+        review.submitted_at,
+        review.author_association,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn sync_pull_reviews(pool: &PgPool, reviews: &[PullReview]) -> anyhow::Result<()> {
+    for review in reviews {
+        upsert_pull_review(pool, review).await?;
     }
+    Ok(())
+}
+
+pub async fn list_pull_reviews(pool: &PgPool, pull_id: &str) -> anyhow::Result<Vec<PullReview>> {
+    let reviews = sqlx::query_as!(
+        PullReview,
+        r#"
+        SELECT review_id, pull_id, reviewer, state, submitted_at, author_association
+        FROM pull_request_reviews
+        WHERE pull_id = $1
+        ORDER BY submitted_at
+        "#,
+        pull_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reviews)
+}
+
+pub async fn add_project_if_missing(
+    pool: &PgPool,
+    project_id: &str,
+    project_logo: &str,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO projects (project_id, project_logo, issues_list)
+        VALUES ($1, $2, ARRAY[]::text[])
+        ON CONFLICT (project_id) DO NOTHING
+        "#,
+        project_id,
+        project_logo,
+    )
+    .execute(pool)
+    .await?;
 
     Ok(())
 }
-*/
-/* pub async fn list_pull_requests(
+
+// `project` narrows the list to one repository
+pub async fn list_pull_requests(
     pool: &sqlx::PgPool,
-) -> anyhow::Result<
-    Vec<(
-        String,
-        String,
-        String,
-        String,
-        String,
-        Vec<String>,
-        Vec<String>,
-    )>,
-> {
+    campaign_id: &str,
+    project: Option<&str>,
+) -> anyhow::Result<Vec<PullRequestRow>> {
     let pull_requests = sqlx::query!(
         r#"
-        SELECT pull_id, title, author, repository, merged_by, cross_referenced_issues, connected_issues
+        SELECT pull_id, title, author, repository, merged_by, labels, created_at, merged_at,
+            additions, deletions, spam_score
         FROM pull_requests
-        "#
+        WHERE campaign_id = $1 AND ($2::VARCHAR IS NULL OR repository = $2)
+        ORDER BY pull_id
+        "#,
+        campaign_id,
+        project.map(|project| project.trim_end_matches('/')),
     )
     .fetch_all(pool)
     .await?
-    .iter()
-    .map(|r| {
-        (
-            r.pull_id.clone(),
-            r.title.clone(),
-            r.author.clone(),
-            r.repository.clone(),
-            r.merged_by.clone(),
-            r.cross_referenced_issues.clone().unwrap_or_default(), // Handle Option<Vec<String>>
-            r.connected_issues.clone().unwrap_or_default(), // Handle Option<Vec<String>>
-        )
+    .into_iter()
+    .map(|r| PullRequestRow {
+        pull_id: r.pull_id,
+        title: r.title,
+        author: r.author,
+        repository: r.repository,
+        merged_by: r.merged_by,
+        labels: r.labels.unwrap_or_default(),
+        created_at: r.created_at,
+        merged_at: r.merged_at,
+        additions: r.additions,
+        deletions: r.deletions,
+        spam_score: r.spam_score,
     })
     .collect();
 
     Ok(pull_requests)
 }
+
 pub async fn pull_request_exists(pool: &sqlx::PgPool, pull_id: &str) -> anyhow::Result<bool> {
     let exists = sqlx::query!(
         r#"
@@ -352,61 +846,1542 @@ pub async fn pull_request_exists(pool: &sqlx::PgPool, pull_id: &str) -> anyhow::
 
     Ok(exists)
 }
-pub async fn add_pull_request_checked(
-    pool: &sqlx::PgPool,
-    pull_id: &str,
-    title: &str,
-    author: &str,
-    repository: &str,
-    merged_by: &str,
-    cross_referenced_issues: &Vec<String>,
-    connected_issues: &Vec<String>,
-) -> anyhow::Result<()> {
-    let exists = pull_request_exists(pool, pull_id).await?;
 
-    if !exists {
-        add_pull_request(
-            pool,
-            pull_id,
-            title,
-            author,
-            repository,
-            merged_by,
-            cross_referenced_issues,
-            connected_issues,
-        )
-        .await?;
+// safe to rerun, a pull_request seen again gets its labels, reviews and merge state refreshed
+// a pull_request stays in the campaign it was first stored under
+pub async fn upsert_pull_request(
+    pool: &PgPool,
+    campaign_id: &str,
+    pull: &OuterPull,
+) -> anyhow::Result<()> {
+    for login in pull.author.iter().chain(pull.merged_by.iter()) {
+        add_contributor_stub(pool, login).await?;
     }
 
+    sqlx::query!(
+        r#"
+        INSERT INTO pull_requests (pull_id, title, author, repository, merged_by, cross_referenced_issues, connected_issues, labels, approving_reviewers, created_at, merged_at, additions, deletions, changed_files, commit_count, campaign_id)
+        VALUES ($1, $2, $3, $4, $5,
+            ARRAY(SELECT issue_id FROM issue_references WHERE source_id = $1::VARCHAR),
+            $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        ON CONFLICT (pull_id) DO UPDATE
+        SET title = EXCLUDED.title,
+            author = EXCLUDED.author,
+            merged_by = EXCLUDED.merged_by,
+            connected_issues = EXCLUDED.connected_issues,
+            labels = EXCLUDED.labels,
+            approving_reviewers = EXCLUDED.approving_reviewers,
+            merged_at = EXCLUDED.merged_at,
+            additions = EXCLUDED.additions,
+            deletions = EXCLUDED.deletions,
+            changed_files = EXCLUDED.changed_files,
+            commit_count = EXCLUDED.commit_count
+        "#,
+        pull.url,
+        pull.title,
+        pull.author,
+        pull.repository,
+        pull.merged_by,
+        &pull.connected_issues,
+        &pull.labels,
+        &pull.reviews,
+        pull.created_at,
+        pull.merged_at,
+        pull.additions,
+        pull.deletions,
+        pull.changed_files,
+        pull.commit_count,
+        campaign_id,
+    )
+    .execute(pool)
+    .await?;
+
     Ok(())
 }
 
-pub async fn add_pull_request(
-    pool: &PgPool,
-    pull_id: &str,
-    title: &str,
-    author: &str,
-    repository: &str,
-    merged_by: &str,
-    cross_referenced_issues: &Vec<String>,
-    connected_issues: &Vec<String>,
-) -> anyhow::Result<()> {
+// links a merged pull_request to the issues it closes, without overriding an existing link
+pub async fn link_pull_request_to_issues(pool: &PgPool, pull: &OuterPull) -> anyhow::Result<()> {
+    let issue_ids = pull
+        .issue_links
+        .iter()
+        .map(|link| link.issue_id.clone())
+        .collect::<Vec<String>>();
+
     sqlx::query!(
         r#"
-        INSERT INTO pull_requests (pull_id, title, author, repository, merged_by, cross_referenced_issues, connected_issues)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        UPDATE issues
+        SET issue_linked_pr = $1
+        WHERE issue_id = ANY($2)
+            AND issue_linked_pr IS NULL
         "#,
-        pull_id,
-        title,
-        author,
-        repository,
-        merged_by,
-        cross_referenced_issues,
-        connected_issues,
+        pull.url,
+        &issue_ids,
     )
     .execute(pool)
     .await?;
+
     Ok(())
 }
-*/
- */
\ No newline at end of file
+
+// pull_requests by bots are stored for the record but never become the pull_request of an issue
+pub async fn save_pull_request(
+    pool: &PgPool,
+    campaign_id: &str,
+    pull: &OuterPull,
+    bots: &BotFilter,
+) -> anyhow::Result<()> {
+    if project_opted_out(pool, &pull.repository).await? {
+        return Ok(());
+    }
+
+    add_project_if_missing(pool, &pull.repository, &pull.repository_avatar).await?;
+    add_campaign_project(pool, campaign_id, &pull.repository).await?;
+    upsert_pull_request(pool, campaign_id, pull).await?;
+    sync_pull_reviews(pool, &pull.review_history).await?;
+    sync_pull_issue_links(pool, &pull.url, &pull.issue_links).await?;
+    sync_pull_files(pool, &pull.url, &pull.files).await?;
+
+    if let Some(checks) = &pull.checks {
+        sync_pull_checks(pool, &pull.url, checks).await?;
+    }
+
+    let by_bot = pull
+        .author
+        .as_deref()
+        .is_some_and(|author| bots.is_bot(author));
+    if pull.merged_at.is_some() && !by_bot {
+        link_pull_request_to_issues(pool, pull).await?;
+    }
+
+    Ok(())
+}
+
+// the file list is replaced as a whole, a force-push can drop files from a pull_request
+pub async fn sync_pull_files(
+    pool: &PgPool,
+    pull_id: &str,
+    files: &[PullFile],
+) -> anyhow::Result<()> {
+    let paths = files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect::<Vec<String>>();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM pull_request_files
+        WHERE pull_id = $1 AND NOT (path = ANY($2))
+        "#,
+        pull_id,
+        &paths,
+    )
+    .execute(pool)
+    .await?;
+
+    for file in files {
+        sqlx::query!(
+            r#"
+            INSERT INTO pull_request_files (pull_id, path, additions, deletions)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (pull_id, path) DO UPDATE
+            SET additions = EXCLUDED.additions,
+                deletions = EXCLUDED.deletions
+            "#,
+            pull_id,
+            file.path,
+            file.additions,
+            file.deletions,
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn list_pull_files(pool: &PgPool, pull_id: &str) -> anyhow::Result<Vec<PullFile>> {
+    let files = sqlx::query_as!(
+        PullFile,
+        r#"
+        SELECT path, additions, deletions
+        FROM pull_request_files
+        WHERE pull_id = $1
+        ORDER BY path
+        "#,
+        pull_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(files)
+}
+
+pub async fn sync_pull_checks(
+    pool: &PgPool,
+    pull_id: &str,
+    checks: &PullChecks,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE pull_requests
+        SET ci_commit = $2, ci_state = $3
+        WHERE pull_id = $1
+        "#,
+        pull_id,
+        checks.commit_url,
+        checks.rollup_state,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM pull_request_checks
+        WHERE pull_id = $1
+        "#,
+        pull_id,
+    )
+    .execute(pool)
+    .await?;
+
+    for check in &checks.checks {
+        sqlx::query!(
+            r#"
+            INSERT INTO pull_request_checks (pull_id, name, kind, state, is_required)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (pull_id, kind, name) DO UPDATE
+            SET state = EXCLUDED.state,
+                is_required = EXCLUDED.is_required
+            "#,
+            pull_id,
+            check.name,
+            check.kind,
+            check.state,
+            check.is_required,
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn list_pull_checks(pool: &PgPool, pull_id: &str) -> anyhow::Result<Vec<PullCheck>> {
+    let checks = sqlx::query_as!(
+        PullCheck,
+        r#"
+        SELECT name, kind, state, is_required
+        FROM pull_request_checks
+        WHERE pull_id = $1
+        ORDER BY kind, name
+        "#,
+        pull_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(checks)
+}
+
+pub async fn sync_assignment_events(
+    pool: &PgPool,
+    events: &[AssignmentEvent],
+) -> anyhow::Result<()> {
+    for event in events {
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_assignment_events (event_id, issue_id, assignee, actor, event, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (event_id) DO NOTHING
+            "#,
+            event.event_id,
+            event.issue_id,
+            event.assignee,
+            event.actor,
+            event.event,
+            event.created_at,
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn list_assignment_events(
+    pool: &PgPool,
+    issue_id: &str,
+) -> anyhow::Result<Vec<AssignmentEvent>> {
+    let events = sqlx::query_as!(
+        AssignmentEvent,
+        r#"
+        SELECT event_id, issue_id, assignee, actor, event, created_at
+        FROM issue_assignment_events
+        WHERE issue_id = $1
+        ORDER BY created_at
+        "#,
+        issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
+
+// issue_assignee is derived from the history, the earliest assignee still holding the issue
+// who isn't a bot
+pub async fn refresh_issue_assignee(
+    pool: &PgPool,
+    issue_id: &str,
+    bots: &BotFilter,
+) -> anyhow::Result<()> {
+    let events = list_assignment_events(pool, issue_id).await?;
+    let assignee = current_assignees(&events)
+        .into_iter()
+        .find(|assignee| !bots.is_bot(assignee));
+    if let Some(login) = &assignee {
+        add_contributor_stub(pool, login).await?;
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE issues
+        SET issue_assignee = $2
+        WHERE issue_id = $1
+        "#,
+        issue_id,
+        assignee,
+    )
+    .execute(pool)
+    .await?;
+
+    clear_stale_claims(pool, Some(issue_id)).await?;
+
+    Ok(())
+}
+
+// who held the issue at the moment the pull_request was opened, empty if it was unassigned then;
+// bots holding it are left out, None for a pull_request that isn't stored
+pub async fn assignees_when_pull_opened(
+    pool: &PgPool,
+    issue_id: &str,
+    pull_id: &str,
+    bots: &BotFilter,
+) -> anyhow::Result<Option<Vec<String>>> {
+    let opened_at = sqlx::query!(
+        r#"
+        SELECT created_at
+        FROM pull_requests
+        WHERE pull_id = $1
+        "#,
+        pull_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .and_then(|rec| rec.created_at);
+
+    let Some(opened_at) = opened_at else {
+        return Ok(None);
+    };
+    let events = list_assignment_events(pool, issue_id).await?;
+
+    Ok(Some(
+        assignees_at(&events, opened_at)
+            .into_iter()
+            .filter(|assignee| !bots.is_bot(assignee))
+            .collect(),
+    ))
+}
+
+// the login alone, so rows naming a contributor can be stored before the profile is fetched
+pub async fn add_contributor_stub(pool: &PgPool, login: &str) -> anyhow::Result<()> {
+    let stub = Contributor::stub(login);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO contributors (login, is_bot)
+        VALUES ($1, $2)
+        ON CONFLICT (login) DO NOTHING
+        "#,
+        stub.login,
+        stub.is_bot,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn upsert_contributor(pool: &PgPool, contributor: &Contributor) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO contributors (login, database_id, name, company, location, followers, account_created_at, avatar_url, is_bot, is_organization, fetched_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW())
+        ON CONFLICT (login) DO UPDATE
+        SET database_id = EXCLUDED.database_id,
+            name = EXCLUDED.name,
+            company = EXCLUDED.company,
+            location = EXCLUDED.location,
+            followers = EXCLUDED.followers,
+            account_created_at = EXCLUDED.account_created_at,
+            avatar_url = EXCLUDED.avatar_url,
+            is_bot = EXCLUDED.is_bot,
+            is_organization = EXCLUDED.is_organization,
+            fetched_at = EXCLUDED.fetched_at
+        "#,
+        contributor.login,
+        contributor.database_id,
+        contributor.name,
+        contributor.company,
+        contributor.location,
+        contributor.followers,
+        contributor.account_created_at,
+        contributor.avatar_url,
+        contributor.is_bot,
+        contributor.is_organization,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// stubs whose profile was never fetched
+pub async fn list_unfetched_contributors(pool: &PgPool) -> anyhow::Result<Vec<String>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT login
+        FROM contributors
+        WHERE fetched_at IS NULL
+        ORDER BY login
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs.into_iter().map(|r| r.login).collect())
+}
+
+pub async fn get_contributor(pool: &PgPool, login: &str) -> anyhow::Result<Option<Contributor>> {
+    let rec = sqlx::query!(
+        r#"
+        SELECT login, database_id, name, company, location, followers, account_created_at, avatar_url, is_bot, is_organization
+        FROM contributors
+        WHERE login = $1
+        "#,
+        login
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(rec.map(|r| Contributor {
+        login: r.login,
+        database_id: r.database_id,
+        name: r.name,
+        company: r.company,
+        location: r.location,
+        followers: r.followers,
+        account_created_at: r.account_created_at,
+        avatar_url: r.avatar_url,
+        is_bot: r.is_bot,
+        is_organization: r.is_organization,
+    }))
+}
+
+pub async fn set_issue_author(pool: &PgPool, issue_id: &str, author: &str) -> anyhow::Result<()> {
+    add_contributor_stub(pool, author).await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE issues
+        SET issue_author = $2
+        WHERE issue_id = $1
+        "#,
+        issue_id,
+        author,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// everything a contributor did across the tracked projects, None for an unknown login
+pub async fn get_contributor_activity(
+    pool: &PgPool,
+    login: &str,
+) -> anyhow::Result<Option<ContributorActivity>> {
+    let contributor = match get_contributor(pool, login).await? {
+        Some(contributor) => contributor,
+        None => return Ok(None),
+    };
+
+    let issues_authored = sqlx::query!(
+        r#"
+        SELECT issue_id
+        FROM issues
+        WHERE issue_author = $1
+        ORDER BY issue_id
+        "#,
+        login
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.issue_id)
+    .collect();
+
+    let assigned = sqlx::query!(
+        r#"
+        SELECT issue_id, issue_budget, issue_budget_approved
+        FROM issues
+        WHERE issue_assignee = $1
+        ORDER BY issue_id
+        "#,
+        login
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let pulls = sqlx::query!(
+        r#"
+        SELECT pull_id, merged_at
+        FROM pull_requests
+        WHERE author = $1
+        ORDER BY created_at
+        "#,
+        login
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let comment_count = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM comments
+        WHERE creator = $1 AND NOT deleted
+        "#,
+        login
+    )
+    .fetch_one(pool)
+    .await?
+    .count;
+
+    Ok(Some(ContributorActivity {
+        contributor,
+        issues_authored,
+        issues_assigned: assigned.iter().map(|r| r.issue_id.clone()).collect(),
+        pulls_authored: pulls.iter().map(|r| r.pull_id.clone()).collect(),
+        pulls_merged: pulls
+            .iter()
+            .filter(|r| r.merged_at.is_some())
+            .map(|r| r.pull_id.clone())
+            .collect(),
+        comment_count,
+        payouts: assigned
+            .iter()
+            .filter(|r| r.issue_budget_approved == Some(true))
+            .filter_map(|r| r.issue_budget.map(|budget| (r.issue_id.clone(), budget)))
+            .collect(),
+    }))
+}
+
+// contributors ranked by merged pull_requests, then by lines changed; bots are left out unless
+// allow-listed and deny-listed accounts are left out too
+pub async fn list_leaderboard(
+    pool: &PgPool,
+    bots: &BotFilter,
+) -> anyhow::Result<Vec<LeaderboardRow>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT p.author AS "author!", COUNT(*) AS "merged!",
+            COALESCE(SUM(p.additions + p.deletions), 0) AS "lines!"
+        FROM pull_requests p
+        JOIN contributors c ON c.login = p.author
+        WHERE p.merged_at IS NOT NULL
+            AND NOT (lower(c.login) IN (SELECT lower(l) FROM unnest($2::TEXT[]) l))
+            AND (NOT c.is_bot OR lower(c.login) IN (SELECT lower(l) FROM unnest($1::TEXT[]) l))
+        GROUP BY p.author
+        ORDER BY 2 DESC, 3 DESC, p.author
+        "#,
+        &bots.allow,
+        &bots.deny,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs
+        .into_iter()
+        .map(|r| LeaderboardRow {
+            author: r.author,
+            merged: r.merged,
+            lines: r.lines,
+        })
+        .collect())
+}
+
+// authors of the given pull_requests whose GitHub profile hasn't been fetched yet
+pub async fn list_unprofiled_authors(
+    pool: &PgPool,
+    pull_ids: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT DISTINCT p.author AS "author!"
+        FROM pull_requests p
+        LEFT JOIN contributors c ON c.login = p.author
+        WHERE p.pull_id = ANY($1) AND p.author IS NOT NULL AND c.fetched_at IS NULL
+        ORDER BY 1
+        "#,
+        pull_ids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs.into_iter().map(|r| r.author).collect())
+}
+
+// everything the spam scorer needs that is already stored, `whitespace_only` is left false for
+// the caller to fill in from the diff
+pub async fn get_spam_signals(pool: &PgPool, pull_id: &str) -> anyhow::Result<SpamSignals> {
+    // owners are counted rather than repos, a spree across one org's repos is still one project
+    let rec = sqlx::query!(
+        r#"
+        SELECT p.created_at AS "opened_at!", COALESCE(p.additions, 0) AS "additions!",
+            c.account_created_at,
+            (SELECT COUNT(DISTINCT split_part(o.repository, '/', 4)) FROM pull_requests o
+                WHERE o.author = p.author
+                    AND o.created_at BETWEEN p.created_at - make_interval(hours => $2)
+                        AND p.created_at + make_interval(hours => $2)) AS "owners_in_window!",
+            (SELECT COUNT(*) FROM pull_requests t
+                WHERE lower(trim(t.title)) = lower(trim(p.title))
+                    AND t.pull_id <> p.pull_id) AS "same_title_count!"
+        FROM pull_requests p
+        LEFT JOIN contributors c ON c.login = p.author
+        WHERE p.pull_id = $1 AND p.created_at IS NOT NULL
+        "#,
+        pull_id,
+        BURST_WINDOW_HOURS as i32,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("no opening time recorded for {}", pull_id))?;
+
+    Ok(SpamSignals {
+        files: list_pull_files(pool, pull_id).await?,
+        whitespace_only: false,
+        additions: rec.additions,
+        opened_at: rec.opened_at,
+        account_created_at: rec.account_created_at,
+        owners_in_window: rec.owners_in_window,
+        same_title_count: rec.same_title_count,
+    })
+}
+
+pub async fn save_spam_score(
+    pool: &PgPool,
+    pull_id: &str,
+    score: &SpamScore,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE pull_requests
+        SET spam_score = $2, spam_reasons = $3
+        WHERE pull_id = $1
+        "#,
+        pull_id,
+        score.score,
+        &score.reasons,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn upsert_campaign(pool: &PgPool, campaign: &Campaign) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO campaigns (campaign_id, name, starts_on, ends_on, issue_labels, accepted_pr_labels, excluded_labels, eligible_repos, opt_in_topic, budget_pool, window_days, search_scopes, sync_interval_hours)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        ON CONFLICT (campaign_id) DO UPDATE
+        SET name = EXCLUDED.name,
+            starts_on = EXCLUDED.starts_on,
+            ends_on = EXCLUDED.ends_on,
+            issue_labels = EXCLUDED.issue_labels,
+            accepted_pr_labels = EXCLUDED.accepted_pr_labels,
+            excluded_labels = EXCLUDED.excluded_labels,
+            eligible_repos = EXCLUDED.eligible_repos,
+            opt_in_topic = EXCLUDED.opt_in_topic,
+            budget_pool = EXCLUDED.budget_pool,
+            window_days = EXCLUDED.window_days,
+            search_scopes = EXCLUDED.search_scopes,
+            sync_interval_hours = EXCLUDED.sync_interval_hours
+        "#,
+        campaign.campaign_id,
+        campaign.name,
+        campaign.starts_on,
+        campaign.ends_on,
+        &campaign.issue_labels,
+        &campaign.accepted_pr_labels,
+        &campaign.excluded_labels,
+        &campaign.eligible_repos,
+        campaign.opt_in_topic,
+        campaign.budget_pool,
+        campaign.window_days,
+        &campaign.search_scopes,
+        campaign.sync_interval_hours,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_campaign(pool: &PgPool, campaign_id: &str) -> anyhow::Result<Option<Campaign>> {
+    let campaign = sqlx::query_as!(
+        Campaign,
+        r#"
+        SELECT campaign_id, name, starts_on, ends_on, issue_labels, accepted_pr_labels, excluded_labels, eligible_repos, opt_in_topic, budget_pool, window_days, search_scopes, sync_interval_hours
+        FROM campaigns
+        WHERE campaign_id = $1
+        "#,
+        campaign_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(campaign)
+}
+
+pub async fn list_campaigns(pool: &PgPool) -> anyhow::Result<Vec<Campaign>> {
+    let campaigns = sqlx::query_as!(
+        Campaign,
+        r#"
+        SELECT campaign_id, name, starts_on, ends_on, issue_labels, accepted_pr_labels, excluded_labels, eligible_repos, opt_in_topic, budget_pool, window_days, search_scopes, sync_interval_hours
+        FROM campaigns
+        ORDER BY starts_on, campaign_id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(campaigns)
+}
+
+pub async fn add_campaign_project(
+    pool: &PgPool,
+    campaign_id: &str,
+    project_id: &str,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO campaign_projects (campaign_id, project_id)
+        VALUES ($1, $2)
+        ON CONFLICT (campaign_id, project_id) DO NOTHING
+        "#,
+        campaign_id,
+        project_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// budget_pool minus the budgets already approved on the campaign's issues, `except_issue` is
+// left out so re-approving an issue doesn't count its own budget twice; None when the campaign
+// has no pool set, a budget_pool of 0 leaves approvals unlimited. Runs on the caller's
+// connection, so the check can share a transaction with the approval
+pub async fn campaign_budget_remaining(
+    conn: &mut PgConnection,
+    campaign_id: &str,
+    except_issue: &str,
+) -> anyhow::Result<Option<i64>> {
+    let remaining = sqlx::query!(
+        r#"
+        SELECT CASE WHEN c.budget_pool = 0 THEN NULL
+            ELSE c.budget_pool - COALESCE(SUM(i.issue_budget), 0) END AS "remaining?"
+        FROM campaigns c
+        LEFT JOIN issues i ON i.campaign_id = c.campaign_id
+            AND i.issue_budget_approved
+            AND i.issue_id <> $2
+        WHERE c.campaign_id = $1
+        GROUP BY c.campaign_id, c.budget_pool
+        "#,
+        campaign_id,
+        except_issue,
+    )
+    .fetch_optional(conn)
+    .await?
+    .map(|r| r.remaining)
+    .ok_or_else(|| anyhow::anyhow!("unknown campaign {}", campaign_id))?;
+
+    Ok(remaining)
+}
+
+// the rules of a campaign are replaced as a whole, a rule dropped from the file is dropped here
+pub async fn sync_campaign_rules(
+    pool: &PgPool,
+    campaign_id: &str,
+    rules: &[EligibilityRule],
+) -> anyhow::Result<()> {
+    let names = rules
+        .iter()
+        .map(|rule| rule.name.clone())
+        .collect::<Vec<String>>();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM campaign_rules
+        WHERE campaign_id = $1 AND NOT (rule_name = ANY($2))
+        "#,
+        campaign_id,
+        &names,
+    )
+    .execute(pool)
+    .await?;
+
+    for rule in rules {
+        let applies_to = match rule.applies_to {
+            ItemKind::PullRequest => "pull_request",
+            ItemKind::Issue => "issue",
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO campaign_rules (campaign_id, rule_name, applies_to, predicate)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (campaign_id, rule_name) DO UPDATE
+            SET applies_to = EXCLUDED.applies_to,
+                predicate = EXCLUDED.predicate
+            "#,
+            campaign_id,
+            rule.name,
+            applies_to,
+            serde_json::to_value(&rule.predicate)?,
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn list_campaign_rules(
+    pool: &PgPool,
+    campaign_id: &str,
+) -> anyhow::Result<Vec<EligibilityRule>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT rule_name, applies_to, predicate
+        FROM campaign_rules
+        WHERE campaign_id = $1
+        ORDER BY rule_name
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    recs.into_iter()
+        .map(|r| {
+            Ok(EligibilityRule {
+                applies_to: serde_json::from_value(serde_json::Value::String(r.applies_to))?,
+                predicate: serde_json::from_value(r.predicate)?,
+                name: r.rule_name,
+                campaign_id: Some(campaign_id.to_string()),
+            })
+        })
+        .collect()
+}
+
+pub async fn list_queue_issue_ids(pool: &PgPool, campaign_id: &str) -> anyhow::Result<Vec<String>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT issue_id
+        FROM issues
+        WHERE campaign_id = $1 AND review_status = 'queue'
+        ORDER BY issue_id
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs.into_iter().map(|r| r.issue_id).collect())
+}
+
+// None when the pull_request isn't stored or wasn't scored yet
+pub async fn get_spam_score(pool: &PgPool, pull_id: &str) -> anyhow::Result<Option<i32>> {
+    let rec = sqlx::query!(
+        r#"
+        SELECT spam_score
+        FROM pull_requests
+        WHERE pull_id = $1
+        "#,
+        pull_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(rec.and_then(|r| r.spam_score))
+}
+
+// a closed issue keeps an existing link, the closing pull_request only fills an empty one
+pub async fn close_issue(
+    pool: &PgPool,
+    issue_id: &str,
+    closing_pull: Option<&str>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE issues
+        SET issue_status = 'closed',
+            issue_linked_pr = COALESCE(issue_linked_pr, $2)
+        WHERE issue_id = $1
+        "#,
+        issue_id,
+        closing_pull,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_campaign_issue_ids(
+    pool: &PgPool,
+    campaign_id: &str,
+) -> anyhow::Result<Vec<String>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT issue_id
+        FROM issues
+        WHERE campaign_id = $1
+        ORDER BY issue_id
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs.into_iter().map(|r| r.issue_id).collect())
+}
+
+pub async fn list_campaign_project_ids(
+    pool: &PgPool,
+    campaign_id: &str,
+) -> anyhow::Result<Vec<String>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT project_id
+        FROM campaign_projects
+        WHERE campaign_id = $1
+        ORDER BY project_id
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs.into_iter().map(|r| r.project_id).collect())
+}
+
+// issues of a campaign with everything a payout sheet needs, one json object each
+pub async fn export_issues(
+    pool: &PgPool,
+    campaign_id: &str,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT issue_id, project_id, issue_title, issue_author, issue_assignee, issue_budget,
+            issue_budget_approved, issue_linked_pr, issue_status,
+            review_status AS "review_status: ReviewStatus", reviewed_by
+        FROM issues
+        WHERE campaign_id = $1
+        ORDER BY issue_id
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs
+        .into_iter()
+        .map(|r| {
+            serde_json::json!({
+                "type": "issue",
+                "issue_id": r.issue_id,
+                "project_id": r.project_id,
+                "title": r.issue_title,
+                "author": r.issue_author,
+                "assignee": r.issue_assignee,
+                "budget": r.issue_budget,
+                "budget_approved": r.issue_budget_approved,
+                "linked_pr": r.issue_linked_pr,
+                "status": r.issue_status,
+                "review_status": r.review_status,
+                "reviewed_by": r.reviewed_by,
+            })
+        })
+        .collect())
+}
+
+pub async fn export_pull_requests(
+    pool: &PgPool,
+    campaign_id: &str,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT pull_id, title, author, repository, merged_by, labels, created_at, merged_at,
+            additions, deletions, spam_score
+        FROM pull_requests
+        WHERE campaign_id = $1
+        ORDER BY pull_id
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs
+        .into_iter()
+        .map(|r| {
+            serde_json::json!({
+                "type": "pull_request",
+                "pull_id": r.pull_id,
+                "title": r.title,
+                "author": r.author,
+                "repository": r.repository,
+                "merged_by": r.merged_by,
+                "labels": r.labels,
+                "created_at": r.created_at,
+                "merged_at": r.merged_at,
+                "additions": r.additions,
+                "deletions": r.deletions,
+                "spam_score": r.spam_score,
+            })
+        })
+        .collect())
+}
+
+// stores the plan of a new run, returns its run_id
+pub async fn create_sync_run(
+    pool: &PgPool,
+    campaign_id: &str,
+    target: &str,
+    windows: &[SyncWindow],
+) -> anyhow::Result<i32> {
+    let plan = windows
+        .iter()
+        .map(|window| window.query.clone())
+        .collect::<Vec<String>>();
+
+    let run_id = sqlx::query!(
+        r#"
+        INSERT INTO sync_runs (campaign_id, target, plan)
+        VALUES ($1, $2, $3)
+        RETURNING run_id
+        "#,
+        campaign_id,
+        target,
+        &plan,
+    )
+    .fetch_one(pool)
+    .await?
+    .run_id;
+
+    for window in windows {
+        sqlx::query!(
+            r#"
+            INSERT INTO sync_run_windows (run_id, window_index, search, query, since)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            run_id,
+            window.window_index,
+            window.search as SyncSearch,
+            window.query,
+            window.since,
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(run_id)
+}
+
+// the latest run of the target unless it finished, a run that was killed still says running
+pub async fn get_resumable_sync_run(
+    pool: &PgPool,
+    campaign_id: &str,
+    target: &str,
+) -> anyhow::Result<Option<SyncRun>> {
+    let run = sqlx::query!(
+        r#"
+        SELECT run_id, campaign_id, target, status AS "status: SyncRunStatus"
+        FROM sync_runs
+        WHERE campaign_id = $1 AND target = $2
+        ORDER BY run_id DESC
+        LIMIT 1
+        "#,
+        campaign_id,
+        target,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(run) = run.filter(|run| run.status != SyncRunStatus::Finished) else {
+        return Ok(None);
+    };
+
+    let windows = sqlx::query!(
+        r#"
+        SELECT window_index, search AS "search: SyncSearch", query, cursor, pages, finished,
+            since, max_updated_at, base_query
+        FROM sync_run_windows
+        WHERE run_id = $1
+        ORDER BY window_index
+        "#,
+        run.run_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| SyncWindow {
+        window_index: r.window_index,
+        search: r.search,
+        query: r.query,
+        cursor: r.cursor,
+        pages: r.pages,
+        finished: r.finished,
+        since: r.since,
+        max_updated_at: r.max_updated_at,
+        base_query: r.base_query,
+    })
+    .collect();
+
+    Ok(Some(SyncRun {
+        run_id: run.run_id,
+        campaign_id: run.campaign_id,
+        target: run.target,
+        status: run.status,
+        windows,
+    }))
+}
+
+// a window that hit the search cap hands its query over to `windows`, the narrower ones it was
+// split into; it keeps no watermark of its own, the split windows report theirs under its
+// planned query
+pub async fn split_sync_window(
+    pool: &PgPool,
+    run_id: i32,
+    window_index: i32,
+    windows: &[SyncWindow],
+) -> anyhow::Result<()> {
+    for window in windows {
+        sqlx::query!(
+            r#"
+            INSERT INTO sync_run_windows (run_id, window_index, search, query, since, base_query)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            run_id,
+            window.window_index,
+            window.search as SyncSearch,
+            window.query,
+            window.since,
+            window.base_query,
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    let queries = windows
+        .iter()
+        .map(|window| window.query.clone())
+        .collect::<Vec<String>>();
+    sqlx::query!(
+        r#"
+        UPDATE sync_runs
+        SET plan = array_cat(plan, $2::TEXT[])
+        WHERE run_id = $1
+        "#,
+        run_id,
+        &queries,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE sync_run_windows
+        SET cursor = NULL, finished = TRUE, max_updated_at = NULL
+        WHERE run_id = $1 AND window_index = $2
+        "#,
+        run_id,
+        window_index,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// called once the results of a page are stored, `cursor` is where the next page starts and
+// `updated_at` the newest updatedAt on the page
+pub async fn save_sync_checkpoint(
+    pool: &PgPool,
+    run_id: i32,
+    window_index: i32,
+    cursor: Option<&str>,
+    finished: bool,
+    updated_at: Option<DateTime<Utc>>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE sync_run_windows
+        SET cursor = $3, pages = pages + 1, finished = $4,
+            max_updated_at = GREATEST(max_updated_at, $5)
+        WHERE run_id = $1 AND window_index = $2
+        "#,
+        run_id,
+        window_index,
+        cursor,
+        finished,
+        updated_at,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_sync_run_status(
+    pool: &PgPool,
+    run_id: i32,
+    status: SyncRunStatus,
+    error: Option<&str>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE sync_runs
+        SET status = $2, error = $3,
+            finished_at = CASE WHEN $2 = 'running'::sync_run_status THEN NULL ELSE NOW() END
+        WHERE run_id = $1
+        "#,
+        run_id,
+        status as SyncRunStatus,
+        error,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_sync_watermarks(
+    pool: &PgPool,
+    campaign_id: &str,
+) -> anyhow::Result<HashMap<String, DateTime<Utc>>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT query, updated_at
+        FROM sync_watermarks
+        WHERE campaign_id = $1
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs.into_iter().map(|r| (r.query, r.updated_at)).collect())
+}
+
+// when the last run of `target` for the campaign finished, None before the first one did
+pub async fn last_finished_sync_run_at(
+    pool: &PgPool,
+    campaign_id: &str,
+    target: &str,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let finished_at = sqlx::query!(
+        r#"
+        SELECT MAX(finished_at) AS finished_at
+        FROM sync_runs
+        WHERE campaign_id = $1 AND target = $2 AND status = 'finished'
+        "#,
+        campaign_id,
+        target,
+    )
+    .fetch_one(pool)
+    .await?
+    .finished_at;
+
+    Ok(finished_at)
+}
+
+// marks the run finished and moves the watermark of every query it fetched something for, in
+// one statement so a watermark never moves for a run that didn't finish; the windows split off a
+// planned query move its watermark only as far as the one that got least far. A watermark never
+// passes the start of the run, an item updated after its page was fetched is older than what
+// later pages brought in and the next run has to find it again
+pub async fn finish_sync_run(pool: &PgPool, run_id: i32) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        WITH finished AS (
+            UPDATE sync_runs
+            SET status = 'finished', error = NULL, finished_at = NOW()
+            WHERE run_id = $1
+            RETURNING run_id, campaign_id, started_at
+        )
+        INSERT INTO sync_watermarks (campaign_id, query, updated_at)
+        SELECT f.campaign_id, COALESCE(w.base_query, w.query),
+            LEAST(MIN(w.max_updated_at), f.started_at)
+        FROM finished f
+        JOIN sync_run_windows w ON w.run_id = f.run_id
+        WHERE w.max_updated_at IS NOT NULL
+        GROUP BY f.campaign_id, f.started_at, COALESCE(w.base_query, w.query)
+        ON CONFLICT (campaign_id, query) DO UPDATE
+        SET updated_at = GREATEST(sync_watermarks.updated_at, EXCLUDED.updated_at)
+        "#,
+        run_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_runs::plan_windows;
+    use std::sync::atomic::AtomicI32;
+
+    const PULL: &str = "https://github.com/owner/repo/pull/9";
+    const KEPT_ISSUE: &str = "https://github.com/owner/repo/issues/1";
+    const UNLINKED_ISSUE: &str = "https://github.com/owner/repo/issues/2";
+
+    fn link(issue_id: &str, origin: LinkOrigin) -> PullIssueLink {
+        PullIssueLink {
+            pull_id: PULL.to_string(),
+            issue_id: issue_id.to_string(),
+            origin,
+        }
+    }
+
+    async fn seed_issue(pool: &PgPool, issue_id: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO issues (issue_id, project_id, issue_title, issue_description, campaign_id, issue_linked_pr)
+            VALUES ($1, 'https://github.com/owner/repo', 'title', 'body', $2, $3)
+            "#,
+        )
+        .bind(issue_id)
+        .bind(DEFAULT_CAMPAIGN_ID)
+        .bind(PULL)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2024-10-01T{hour:02}:00:00Z"))
+            .expect("valid timestamp")
+            .with_timezone(&Utc)
+    }
+
+    #[sqlx::test]
+    async fn split_windows_move_the_planned_watermark_least_far(
+        pool: PgPool,
+    ) -> anyhow::Result<()> {
+        let campaign = Campaign {
+            starts_on: chrono::NaiveDate::from_ymd_opt(2023, 10, 1).expect("valid date"),
+            ends_on: chrono::NaiveDate::from_ymd_opt(2023, 10, 4).expect("valid date"),
+            window_days: 4,
+            ..Campaign::hacktoberfest(2023)
+        };
+        let windows = plan_windows(&campaign, &[SyncSearch::OpenIssues], &HashMap::new());
+        let planned = windows[0].query.clone();
+        let run_id = create_sync_run(&pool, DEFAULT_CAMPAIGN_ID, "issues", &windows).await?;
+
+        let next_index = AtomicI32::new(1);
+        let [first, second] = windows[0].split(&next_index).expect("four days");
+        save_sync_checkpoint(&pool, run_id, 0, Some("cursor"), false, Some(at(12))).await?;
+        split_sync_window(&pool, run_id, 0, &[first.clone(), second.clone()]).await?;
+        save_sync_checkpoint(&pool, run_id, first.window_index, None, true, Some(at(9))).await?;
+        save_sync_checkpoint(&pool, run_id, second.window_index, None, true, Some(at(11))).await?;
+        finish_sync_run(&pool, run_id).await?;
+
+        let watermarks = list_sync_watermarks(&pool, DEFAULT_CAMPAIGN_ID).await?;
+        assert_eq!(watermarks.len(), 1);
+        assert_eq!(watermarks.get(&planned), Some(&at(9)));
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn the_watermark_stops_at_the_start_of_the_run(pool: PgPool) -> anyhow::Result<()> {
+        let windows = plan_windows(
+            &Campaign::hacktoberfest(2023),
+            &[SyncSearch::OpenIssues],
+            &HashMap::new(),
+        );
+        let run_id = create_sync_run(&pool, DEFAULT_CAMPAIGN_ID, "issues", &windows).await?;
+        // an item updated while the run was going
+        let during_run = Utc::now() + chrono::Duration::minutes(5);
+        save_sync_checkpoint(&pool, run_id, 0, None, true, Some(during_run)).await?;
+        finish_sync_run(&pool, run_id).await?;
+
+        let started_at: DateTime<Utc> =
+            sqlx::query_scalar("SELECT started_at FROM sync_runs WHERE run_id = $1")
+                .bind(run_id)
+                .fetch_one(&pool)
+                .await?;
+        let watermarks = list_sync_watermarks(&pool, DEFAULT_CAMPAIGN_ID).await?;
+        assert_eq!(watermarks.get(&windows[0].query), Some(&started_at));
+        Ok(())
+    }
+
+    async fn run(pool: &PgPool, sql: &str, issue_id: &str) -> anyhow::Result<()> {
+        sqlx::query(sql).bind(issue_id).execute(pool).await?;
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn contributor_activity_covers_issues_pulls_and_comments(
+        pool: PgPool,
+    ) -> anyhow::Result<()> {
+        assert!(get_contributor_activity(&pool, "alice").await?.is_none());
+
+        add_contributor_stub(&pool, "alice").await?;
+        seed_issue(&pool, KEPT_ISSUE).await?;
+        seed_issue(&pool, UNLINKED_ISSUE).await?;
+        set_issue_author(&pool, KEPT_ISSUE, "alice").await?;
+        run(
+            &pool,
+            r#"
+            UPDATE issues
+            SET issue_assignee = 'alice', issue_budget = 50, issue_budget_approved = TRUE
+            WHERE issue_id = $1
+            "#,
+            UNLINKED_ISSUE,
+        )
+        .await?;
+        run(
+            &pool,
+            r#"
+            INSERT INTO comments (comment_id, issue_id, creator, content, deleted)
+            VALUES ('comment-1', $1, 'alice', 'on it', FALSE), ('comment-2', $1, 'alice', 'gone', TRUE)
+            "#,
+            KEPT_ISSUE,
+        )
+        .await?;
+        run(
+            &pool,
+            r#"
+            INSERT INTO pull_requests (pull_id, title, repository, campaign_id, author, created_at, merged_at)
+            VALUES ($1, 'fix', 'https://github.com/owner/repo', 'hacktoberfest-2023', 'alice', NOW(), NOW())
+            "#,
+            PULL,
+        )
+        .await?;
+
+        let activity = get_contributor_activity(&pool, "alice")
+            .await?
+            .expect("stored contributor");
+        assert_eq!(activity.issues_authored, vec![KEPT_ISSUE]);
+        assert_eq!(activity.issues_assigned, vec![UNLINKED_ISSUE]);
+        assert_eq!(activity.pulls_authored, vec![PULL]);
+        assert_eq!(activity.pulls_merged, vec![PULL]);
+        assert_eq!(activity.comment_count, 1);
+        assert_eq!(activity.payouts, vec![(UNLINKED_ISSUE.to_string(), 50)]);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn credits_whoever_held_the_issue_when_the_pull_was_opened(
+        pool: PgPool,
+    ) -> anyhow::Result<()> {
+        seed_issue(&pool, KEPT_ISSUE).await?;
+        for login in ["alice", "bob", "helper[bot]"] {
+            add_contributor_stub(&pool, login).await?;
+        }
+        for (event, assignee, days_ago) in [
+            ("assigned", "alice", 10),
+            ("assigned", "helper[bot]", 9),
+            ("unassigned", "alice", 2),
+            ("assigned", "bob", 1),
+        ] {
+            sqlx::query(
+                r#"
+                INSERT INTO issue_assignment_events (event_id, issue_id, assignee, actor, event, created_at)
+                VALUES ($1, $2, $3, 'maintainer', $4, NOW() - make_interval(days => $5))
+                "#,
+            )
+            .bind(format!("{event}/{assignee}"))
+            .bind(KEPT_ISSUE)
+            .bind(assignee)
+            .bind(event)
+            .bind(days_ago)
+            .execute(&pool)
+            .await?;
+        }
+        run(
+            &pool,
+            r#"
+            INSERT INTO pull_requests (pull_id, title, repository, campaign_id, created_at)
+            VALUES ($1, 'fix', 'https://github.com/owner/repo', 'hacktoberfest-2023', NOW() - INTERVAL '5 days')
+            "#,
+            PULL,
+        )
+        .await?;
+
+        let bots = BotFilter::default();
+        assert_eq!(
+            assignees_when_pull_opened(&pool, KEPT_ISSUE, PULL, &bots).await?,
+            Some(vec![String::from("alice")])
+        );
+        assert_eq!(
+            assignees_when_pull_opened(&pool, KEPT_ISSUE, "https://github.com/o/r/pull/404", &bots)
+                .await?,
+            None
+        );
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn a_disconnected_issue_loses_its_link(pool: PgPool) -> anyhow::Result<()> {
+        seed_issue(&pool, KEPT_ISSUE).await?;
+        seed_issue(&pool, UNLINKED_ISSUE).await?;
+        sync_pull_issue_links(
+            &pool,
+            PULL,
+            &[
+                link(KEPT_ISSUE, LinkOrigin::Keyword),
+                link(UNLINKED_ISSUE, LinkOrigin::Connected),
+            ],
+        )
+        .await?;
+        add_pull_issue_link(&pool, &link(KEPT_ISSUE, LinkOrigin::CrossReference)).await?;
+
+        sync_pull_issue_links(&pool, PULL, &[link(KEPT_ISSUE, LinkOrigin::Keyword)]).await?;
+
+        let mut origins = list_issue_links(&pool, KEPT_ISSUE)
+            .await?
+            .into_iter()
+            .map(|link| link.origin.to_string())
+            .collect::<Vec<String>>();
+        origins.sort();
+        assert_eq!(origins, vec!["cross_reference", "keyword"]);
+        assert!(list_issue_links(&pool, UNLINKED_ISSUE).await?.is_empty());
+
+        let linked_pr = |issue_id: &'static str| {
+            sqlx::query_scalar::<_, Option<String>>(
+                "SELECT issue_linked_pr FROM issues WHERE issue_id = $1",
+            )
+            .bind(issue_id)
+            .fetch_one(&pool)
+        };
+        assert_eq!(linked_pr(KEPT_ISSUE).await?.as_deref(), Some(PULL));
+        assert_eq!(linked_pr(UNLINKED_ISSUE).await?, None);
+        Ok(())
+    }
+}
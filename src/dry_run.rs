@@ -0,0 +1,415 @@
+use serde_json::Value;
+use sqlx::postgres::{PgPool, PgPoolOptions, PgTransactionManager};
+use sqlx::{Row, TransactionManager};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// a custom setting only the dry run's transaction carries
+const DRY_RUN_SETTING: &str = "tracker.dry_run";
+
+// columns whose changes are summed up as transitions, e.g. issues going from open to closed
+pub const STATE_COLUMNS: &[(&str, &str)] = &[
+    ("issues", "issue_status"),
+    ("issues", "review_status"),
+    ("issues", "issue_budget_approved"),
+    ("projects", "opted_in"),
+    ("comments", "deleted"),
+    ("eligibility_results", "passed"),
+];
+
+// a dry run holds every write in one transaction on a single connection, the write functions
+// keep taking the pool and can't tell the difference; `rollback` throws the writes away.
+// The transaction is opened as the connection is made and checked before every use, a
+// connection that left it is dropped and no second connection is let in, so the command fails
+// rather than writing outside the transaction
+pub async fn connect_dry_run(database_url: &str) -> anyhow::Result<PgPool> {
+    let connected = Arc::new(AtomicBool::new(false));
+
+    let pool = PgPoolOptions::new()
+        .min_connections(1)
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .after_connect(move |conn, _| {
+            let connected = connected.clone();
+            Box::pin(async move {
+                if connected.swap(true, Ordering::SeqCst) {
+                    return Err(sqlx::Error::Protocol(String::from(
+                        "dry run lost its transaction",
+                    )));
+                }
+                // begun through sqlx so it knows the connection is in a transaction, a write
+                // function's own `begin` and `commit` become a savepoint and its release
+                PgTransactionManager::begin(conn).await?;
+                // repeatable read, so the snapshots only differ by our own writes
+                sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+                    .execute(&mut *conn)
+                    .await?;
+                // SET LOCAL ends with the transaction, whichever way it ends
+                sqlx::query(&format!("SET LOCAL {DRY_RUN_SETTING} = 'on'"))
+                    .execute(&mut *conn)
+                    .await?;
+                Ok(())
+            })
+        })
+        .before_acquire(|conn, _| {
+            Box::pin(async move {
+                let in_dry_run: bool = sqlx::query_scalar(&format!(
+                    "SELECT current_setting('{DRY_RUN_SETTING}', TRUE) IS NOT DISTINCT FROM 'on'"
+                ))
+                .fetch_one(&mut *conn)
+                .await?;
+                Ok(in_dry_run)
+            })
+        })
+        .connect(database_url)
+        .await?;
+
+    Ok(pool)
+}
+
+pub async fn rollback(pool: &PgPool) -> anyhow::Result<()> {
+    sqlx::query("ROLLBACK").execute(pool).await?;
+    pool.close().await;
+
+    Ok(())
+}
+
+// table -> primary key -> row
+pub type Snapshot = BTreeMap<String, BTreeMap<String, Value>>;
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// rows are keyed by their primary key columns joined with " / ", a table without one by the
+// whole row
+fn row_key(row: &Value, key_columns: &[String]) -> String {
+    if key_columns.is_empty() {
+        return row.to_string();
+    }
+
+    key_columns
+        .iter()
+        .map(|column| match &row[column] {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" / ")
+}
+
+// every row of the given tables, the ones the command writes to
+pub async fn take_snapshot(pool: &PgPool, tables: &[&str]) -> anyhow::Result<Snapshot> {
+    let tables = sqlx::query!(
+        r#"
+        SELECT tablename AS "tablename!"
+        FROM pg_tables
+        WHERE schemaname = current_schema() AND tablename::TEXT = ANY($1::TEXT[])
+        ORDER BY tablename
+        "#,
+        &tables
+            .iter()
+            .map(|table| table.to_string())
+            .collect::<Vec<String>>()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut snapshot = Snapshot::new();
+    for table in tables {
+        let table = table.tablename;
+        let key_columns = sqlx::query!(
+            r#"
+            SELECT a.attname::TEXT AS "column!"
+            FROM pg_index i
+            JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+            WHERE i.indrelid = $1::TEXT::regclass AND i.indisprimary
+            ORDER BY array_position(i.indkey, a.attnum)
+            "#,
+            quote_ident(&table)
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| r.column)
+        .collect::<Vec<String>>();
+
+        let rows = sqlx::query(&format!(
+            "SELECT to_jsonb(t) FROM {} t",
+            quote_ident(&table)
+        ))
+        .fetch_all(pool)
+        .await?;
+
+        let mut by_key = BTreeMap::new();
+        for row in rows {
+            let row: Value = row.try_get(0)?;
+            by_key.insert(row_key(&row, &key_columns), row);
+        }
+        snapshot.insert(table, by_key);
+    }
+
+    Ok(snapshot)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnChange {
+    pub column: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TableDiff {
+    pub table: String,
+    pub inserted: Vec<String>,
+    pub updated: Vec<(String, Vec<ColumnChange>)>,
+    pub deleted: Vec<String>,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty() && self.deleted.is_empty()
+    }
+
+    // (column, before, after) -> how many rows made that change
+    pub fn transitions(&self) -> BTreeMap<(String, String, String), usize> {
+        let mut transitions = BTreeMap::new();
+        for (_, changes) in &self.updated {
+            for change in changes {
+                if STATE_COLUMNS.contains(&(self.table.as_str(), change.column.as_str())) {
+                    *transitions
+                        .entry((
+                            change.column.clone(),
+                            change.before.to_string(),
+                            change.after.to_string(),
+                        ))
+                        .or_insert(0) += 1;
+                }
+            }
+        }
+        transitions
+    }
+}
+
+fn changed_columns(before: &Value, after: &Value) -> Vec<ColumnChange> {
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else {
+        return Vec::new();
+    };
+
+    after
+        .iter()
+        .filter_map(|(column, value)| {
+            let old = before.get(column).cloned().unwrap_or(Value::Null);
+            (old != *value).then(|| ColumnChange {
+                column: column.clone(),
+                before: old,
+                after: value.clone(),
+            })
+        })
+        .collect()
+}
+
+// net changes between two snapshots, a row written and put back the way it was is no change
+pub fn diff_snapshots(before: &Snapshot, after: &Snapshot) -> Vec<TableDiff> {
+    let empty = BTreeMap::new();
+    let tables = before
+        .keys()
+        .chain(after.keys())
+        .collect::<std::collections::BTreeSet<&String>>();
+
+    tables
+        .into_iter()
+        .map(|table| {
+            let old = before.get(table).unwrap_or(&empty);
+            let new = after.get(table).unwrap_or(&empty);
+
+            let mut diff = TableDiff {
+                table: table.clone(),
+                ..TableDiff::default()
+            };
+            for (key, row) in new {
+                match old.get(key) {
+                    None => diff.inserted.push(key.clone()),
+                    Some(old_row) => {
+                        let changes = changed_columns(old_row, row);
+                        if !changes.is_empty() {
+                            diff.updated.push((key.clone(), changes));
+                        }
+                    }
+                }
+            }
+            diff.deleted = old
+                .keys()
+                .filter(|key| !new.contains_key(*key))
+                .cloned()
+                .collect();
+            diff
+        })
+        .filter(|diff| !diff.is_empty())
+        .collect()
+}
+
+pub fn print_diff(diffs: &[TableDiff]) {
+    if diffs.is_empty() {
+        println!("dry run: nothing would change");
+        return;
+    }
+
+    println!("dry run: nothing was written, this is what would change");
+    for diff in diffs {
+        println!(
+            "{}: {} inserted, {} updated, {} deleted",
+            diff.table,
+            diff.inserted.len(),
+            diff.updated.len(),
+            diff.deleted.len()
+        );
+        for key in &diff.inserted {
+            println!("  + {key}");
+        }
+        for (key, changes) in &diff.updated {
+            let changes = changes
+                .iter()
+                .map(|change| format!("{} {} -> {}", change.column, change.before, change.after))
+                .collect::<Vec<String>>();
+            println!("  ~ {key}: {}", changes.join(", "));
+        }
+        for key in &diff.deleted {
+            println!("  - {key}");
+        }
+        for ((column, before, after), count) in diff.transitions() {
+            println!("  {column}: {before} -> {after} x{count}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(tables: &[(&str, Vec<(&str, Value)>)]) -> Snapshot {
+        tables
+            .iter()
+            .map(|(table, rows)| {
+                let rows = rows
+                    .iter()
+                    .map(|(key, row)| (key.to_string(), row.clone()))
+                    .collect();
+                (table.to_string(), rows)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rows_are_keyed_by_their_primary_key() {
+        let row = json!({"campaign_id": "c", "project_id": "p", "n": 1});
+        let key_columns = vec![String::from("campaign_id"), String::from("n")];
+        assert_eq!(row_key(&row, &key_columns), "c / 1");
+        assert_eq!(row_key(&json!({"n": 1}), &[]), r#"{"n":1}"#);
+    }
+
+    #[test]
+    fn reports_inserted_updated_and_deleted_rows() {
+        let before = snapshot(&[(
+            "issues",
+            vec![
+                ("a", json!({"issue_status": "open", "title": "A"})),
+                ("b", json!({"issue_status": "open", "title": "B"})),
+            ],
+        )]);
+        let after = snapshot(&[(
+            "issues",
+            vec![
+                ("a", json!({"issue_status": "closed", "title": "A"})),
+                ("c", json!({"issue_status": "open", "title": "C"})),
+            ],
+        )]);
+
+        let diffs = diff_snapshots(&before, &after);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].table, "issues");
+        assert_eq!(diffs[0].inserted, vec!["c"]);
+        assert_eq!(diffs[0].deleted, vec!["b"]);
+        assert_eq!(
+            diffs[0].updated,
+            vec![(
+                String::from("a"),
+                vec![ColumnChange {
+                    column: String::from("issue_status"),
+                    before: json!("open"),
+                    after: json!("closed"),
+                }]
+            )]
+        );
+    }
+
+    #[test]
+    fn unchanged_tables_are_left_out() {
+        let rows = vec![("a", json!({"opted_in": true}))];
+        let before = snapshot(&[("projects", rows.clone()), ("comments", vec![])]);
+        let after = snapshot(&[("projects", rows)]);
+        assert!(diff_snapshots(&before, &after).is_empty());
+    }
+
+    #[test]
+    fn new_columns_count_as_changes_from_null() {
+        let before = snapshot(&[("issues", vec![("a", json!({}))])]);
+        let after = snapshot(&[("issues", vec![("a", json!({"stale_at": null, "n": 1}))])]);
+        let diffs = diff_snapshots(&before, &after);
+        assert_eq!(diffs[0].updated[0].1.len(), 1);
+        assert_eq!(diffs[0].updated[0].1[0].column, "n");
+    }
+
+    #[test]
+    fn transitions_count_state_columns_only() {
+        let change = |column: &str, before: Value, after: Value| ColumnChange {
+            column: column.to_string(),
+            before,
+            after,
+        };
+        let diff = TableDiff {
+            table: String::from("issues"),
+            updated: vec![
+                (
+                    String::from("a"),
+                    vec![
+                        change("issue_status", json!("open"), json!("closed")),
+                        change("issue_title", json!("x"), json!("y")),
+                    ],
+                ),
+                (
+                    String::from("b"),
+                    vec![change("issue_status", json!("open"), json!("closed"))],
+                ),
+                (
+                    String::from("c"),
+                    vec![change("issue_budget_approved", Value::Null, json!(true))],
+                ),
+            ],
+            ..TableDiff::default()
+        };
+
+        let transitions = diff.transitions();
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(
+            transitions[&(
+                String::from("issue_status"),
+                String::from("\"open\""),
+                String::from("\"closed\"")
+            )],
+            2
+        );
+        assert_eq!(
+            transitions[&(
+                String::from("issue_budget_approved"),
+                String::from("null"),
+                String::from("true")
+            )],
+            1
+        );
+    }
+}
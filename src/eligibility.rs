@@ -0,0 +1,494 @@
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+
+use crate::bots::BotFilter;
+use crate::campaigns::Campaign;
+use crate::db_updater_local::{list_pull_checks, list_pull_reviews};
+use crate::pull_checks::all_required_checks_succeeded;
+use crate::pull_reviews::has_eligible_approval;
+use crate::spam_score::DEFAULT_SPAM_THRESHOLD;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+// a rule is a tree of predicates, written in config as e.g.
+// {"all": [{"merged": true}, {"not": {"any_label": ["spam", "invalid"]}}]}
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    All(Vec<Predicate>),
+    Any(Vec<Predicate>),
+    Not(Box<Predicate>),
+    Label(String),
+    AnyLabel(Vec<String>),
+    Merged(bool),
+    Approved(bool), // an approval from a maintainer who isn't a bot
+    ChecksPassed(bool),
+    CreatedAfter(DateTime<Utc>),
+    CreatedBefore(DateTime<Utc>),
+    MergedAfter(DateTime<Utc>),
+    MergedBefore(DateTime<Utc>),
+    RepoTopic(String),
+    SpamScoreBelow(i32), // unscored items fail
+    Status(String),      // issue_status of an issue
+    Assigned(bool),
+    BudgetApproved(bool),
+    AuthorIsBot(bool), // by login suffix and the BOT_ALLOWLIST / BOT_DENYLIST overrides
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    PullRequest,
+    Issue,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EligibilityRule {
+    pub name: String,
+    pub applies_to: ItemKind,
+    #[serde(default)]
+    pub campaign_id: Option<String>, // None applies the rule to items of every campaign
+    pub predicate: Predicate,
+}
+
+// what is stored about a pull_request or an issue, facts that don't apply to the kind stay empty
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EligibilityFacts {
+    pub item_id: String,
+    pub campaign_id: String,
+    pub labels: Vec<String>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub approved: bool,
+    pub checks_passed: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub topics: Vec<String>, // of the repository
+    pub spam_score: Option<i32>,
+    pub status: Option<String>,
+    pub assignee: Option<String>,
+    pub budget_approved: bool,
+    pub author_is_bot: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EligibilityResult {
+    pub item_id: String,
+    pub rule_name: String,
+    pub passed: bool,
+    pub failed: Vec<String>, // the predicates that decided a failure
+}
+
+impl Predicate {
+    pub fn describe(&self) -> String {
+        match self {
+            Predicate::All(predicates) => format!("all({})", describe_each(predicates)),
+            Predicate::Any(predicates) => format!("any({})", describe_each(predicates)),
+            Predicate::Not(predicate) => format!("not({})", predicate.describe()),
+            Predicate::Label(label) => format!("label {label}"),
+            Predicate::AnyLabel(labels) => format!("any label of {}", labels.join(", ")),
+            Predicate::Merged(expected) => format!("merged is {expected}"),
+            Predicate::Approved(expected) => format!("approved is {expected}"),
+            Predicate::ChecksPassed(expected) => format!("checks passed is {expected}"),
+            Predicate::CreatedAfter(at) => format!("created after {at}"),
+            Predicate::CreatedBefore(at) => format!("created before {at}"),
+            Predicate::MergedAfter(at) => format!("merged after {at}"),
+            Predicate::MergedBefore(at) => format!("merged before {at}"),
+            Predicate::RepoTopic(topic) => format!("repository topic {topic}"),
+            Predicate::SpamScoreBelow(threshold) => format!("spam score below {threshold}"),
+            Predicate::Status(status) => format!("status {status}"),
+            Predicate::Assigned(expected) => format!("assigned is {expected}"),
+            Predicate::BudgetApproved(expected) => format!("budget approved is {expected}"),
+            Predicate::AuthorIsBot(expected) => format!("author is bot is {expected}"),
+        }
+    }
+
+    // Ok(()) when it holds, otherwise the predicates to blame
+    pub fn evaluate(&self, facts: &EligibilityFacts) -> Result<(), Vec<String>> {
+        let holds = match self {
+            Predicate::All(predicates) => {
+                let failed = predicates
+                    .iter()
+                    .filter_map(|predicate| predicate.evaluate(facts).err())
+                    .flatten()
+                    .collect::<Vec<String>>();
+                return if failed.is_empty() {
+                    Ok(())
+                } else {
+                    Err(failed)
+                };
+            }
+            Predicate::Any(predicates) => {
+                let mut failed = Vec::new();
+                for predicate in predicates {
+                    match predicate.evaluate(facts) {
+                        Ok(()) => return Ok(()),
+                        Err(reasons) => failed.extend(reasons),
+                    }
+                }
+                return Err(failed);
+            }
+            Predicate::Not(predicate) => predicate.evaluate(facts).is_err(),
+            Predicate::Label(label) => has_label(facts, label),
+            Predicate::AnyLabel(labels) => labels.iter().any(|label| has_label(facts, label)),
+            Predicate::Merged(expected) => facts.merged_at.is_some() == *expected,
+            Predicate::Approved(expected) => facts.approved == *expected,
+            Predicate::ChecksPassed(expected) => facts.checks_passed == *expected,
+            Predicate::CreatedAfter(at) => facts.created_at.is_some_and(|created| created >= *at),
+            Predicate::CreatedBefore(at) => facts.created_at.is_some_and(|created| created < *at),
+            Predicate::MergedAfter(at) => facts.merged_at.is_some_and(|merged| merged >= *at),
+            Predicate::MergedBefore(at) => facts.merged_at.is_some_and(|merged| merged < *at),
+            Predicate::RepoTopic(topic) => {
+                facts.topics.iter().any(|t| t.eq_ignore_ascii_case(topic))
+            }
+            Predicate::SpamScoreBelow(threshold) => {
+                facts.spam_score.is_some_and(|score| score < *threshold)
+            }
+            Predicate::Status(status) => facts
+                .status
+                .as_deref()
+                .is_some_and(|s| s.eq_ignore_ascii_case(status)),
+            Predicate::Assigned(expected) => facts.assignee.is_some() == *expected,
+            Predicate::BudgetApproved(expected) => facts.budget_approved == *expected,
+            Predicate::AuthorIsBot(expected) => facts.author_is_bot == *expected,
+        };
+
+        if holds {
+            Ok(())
+        } else {
+            Err(vec![self.describe()])
+        }
+    }
+}
+
+fn describe_each(predicates: &[Predicate]) -> String {
+    predicates
+        .iter()
+        .map(|predicate| predicate.describe())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn has_label(facts: &EligibilityFacts, label: &str) -> bool {
+    facts.labels.iter().any(|l| l.eq_ignore_ascii_case(label))
+}
+
+impl EligibilityRule {
+    pub fn covers(&self, facts: &EligibilityFacts) -> bool {
+        self.campaign_id
+            .as_ref()
+            .is_none_or(|campaign_id| campaign_id == &facts.campaign_id)
+    }
+
+    pub fn evaluate(&self, facts: &EligibilityFacts) -> EligibilityResult {
+        let outcome = self.predicate.evaluate(facts);
+
+        EligibilityResult {
+            item_id: facts.item_id.clone(),
+            rule_name: self.name.clone(),
+            passed: outcome.is_ok(),
+            failed: outcome.err().unwrap_or_default(),
+        }
+    }
+}
+
+// what the search strings of a campaign ask GitHub for, checked locally
+pub fn default_rules(campaign: &Campaign) -> Vec<EligibilityRule> {
+    let campaign_end = campaign.ends_on + Duration::days(1);
+
+    vec![EligibilityRule {
+        name: format!("{}-accepted-pull-request", campaign.campaign_id),
+        applies_to: ItemKind::PullRequest,
+        campaign_id: Some(campaign.campaign_id.clone()),
+        predicate: Predicate::All(vec![
+            Predicate::AnyLabel(campaign.accepted_pr_labels.clone()),
+            Predicate::Merged(true),
+            Predicate::Approved(true),
+            Predicate::CreatedAfter(campaign.starts_on.and_time(NaiveTime::MIN).and_utc()),
+            Predicate::CreatedBefore(campaign_end.and_time(NaiveTime::MIN).and_utc()),
+            Predicate::Not(Box::new(Predicate::AnyLabel(
+                campaign.excluded_labels.clone(),
+            ))),
+            Predicate::SpamScoreBelow(DEFAULT_SPAM_THRESHOLD),
+            Predicate::Not(Box::new(Predicate::AuthorIsBot(true))),
+        ]),
+    }]
+}
+
+// rules are kept as json, e.g. the `rules` entry of a config file
+pub fn parse_rules(json: &str) -> anyhow::Result<Vec<EligibilityRule>> {
+    serde_json::from_str(json).map_err(|e| anyhow::anyhow!("Invalid eligibility rules: {}", e))
+}
+
+pub async fn list_pull_request_facts(
+    pool: &PgPool,
+    campaign_id: &str,
+    bots: &BotFilter,
+) -> anyhow::Result<Vec<EligibilityFacts>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT p.pull_id, p.campaign_id, p.author, p.labels, p.merged_at, p.created_at, p.spam_score, pr.topics
+        FROM pull_requests p
+        LEFT JOIN projects pr ON pr.project_id = p.repository
+        WHERE p.campaign_id = $1
+        ORDER BY p.pull_id
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut all_facts = Vec::new();
+    for r in recs {
+        let reviews = list_pull_reviews(pool, &r.pull_id).await?;
+        let checks = list_pull_checks(pool, &r.pull_id).await?;
+
+        all_facts.push(EligibilityFacts {
+            approved: has_eligible_approval(&reviews, bots),
+            checks_passed: all_required_checks_succeeded(&checks),
+            author_is_bot: r
+                .author
+                .as_deref()
+                .is_some_and(|author| bots.is_bot(author)),
+            item_id: r.pull_id,
+            campaign_id: r.campaign_id,
+            labels: r.labels.unwrap_or_default(),
+            merged_at: r.merged_at,
+            created_at: r.created_at,
+            topics: r.topics.unwrap_or_default(),
+            spam_score: r.spam_score,
+            ..Default::default()
+        });
+    }
+
+    Ok(all_facts)
+}
+
+pub async fn list_issue_facts(
+    pool: &PgPool,
+    campaign_id: &str,
+    bots: &BotFilter,
+) -> anyhow::Result<Vec<EligibilityFacts>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT i.issue_id, i.campaign_id, i.issue_author, i.issue_status, i.issue_assignee, i.issue_budget_approved, pr.topics
+        FROM issues i
+        LEFT JOIN projects pr ON pr.project_id = i.project_id
+        WHERE i.campaign_id = $1
+        ORDER BY i.issue_id
+        "#,
+        campaign_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs
+        .into_iter()
+        .map(|r| EligibilityFacts {
+            item_id: r.issue_id,
+            campaign_id: r.campaign_id,
+            topics: r.topics.unwrap_or_default(),
+            status: r.issue_status,
+            assignee: r.issue_assignee,
+            budget_approved: r.issue_budget_approved.unwrap_or(false),
+            author_is_bot: r
+                .issue_author
+                .as_deref()
+                .is_some_and(|author| bots.is_bot(author)),
+            ..Default::default()
+        })
+        .collect())
+}
+
+pub async fn save_eligibility_result(
+    pool: &PgPool,
+    kind: ItemKind,
+    result: &EligibilityResult,
+) -> anyhow::Result<()> {
+    let item_type = match kind {
+        ItemKind::PullRequest => "pull_request",
+        ItemKind::Issue => "issue",
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO eligibility_results (item_id, item_type, rule_name, passed, failed, evaluated_at)
+        VALUES ($1, $2, $3, $4, $5, NOW())
+        ON CONFLICT (item_id, rule_name) DO UPDATE
+        SET passed = EXCLUDED.passed,
+            failed = EXCLUDED.failed,
+            evaluated_at = EXCLUDED.evaluated_at
+        "#,
+        result.item_id,
+        item_type,
+        result.rule_name,
+        result.passed,
+        &result.failed,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// the job: evaluates every rule against every stored item of its kind in the campaign and
+// records the outcome
+pub async fn run_eligibility_rules(
+    pool: &PgPool,
+    campaign_id: &str,
+    rules: &[EligibilityRule],
+    bots: &BotFilter,
+) -> anyhow::Result<Vec<EligibilityResult>> {
+    let mut results = Vec::new();
+
+    for kind in [ItemKind::PullRequest, ItemKind::Issue] {
+        let kind_rules = rules
+            .iter()
+            .filter(|rule| rule.applies_to == kind)
+            .collect::<Vec<&EligibilityRule>>();
+        if kind_rules.is_empty() {
+            continue;
+        }
+
+        let facts = match kind {
+            ItemKind::PullRequest => list_pull_request_facts(pool, campaign_id, bots).await?,
+            ItemKind::Issue => list_issue_facts(pool, campaign_id, bots).await?,
+        };
+
+        for rule in kind_rules {
+            for item in facts.iter().filter(|item| rule.covers(item)) {
+                let result = rule.evaluate(item);
+                save_eligibility_result(pool, kind, &result).await?;
+                results.push(result);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str) -> DateTime<Utc> {
+        date.parse().unwrap()
+    }
+
+    fn merged_pull() -> EligibilityFacts {
+        EligibilityFacts {
+            item_id: String::from("https://github.com/owner/repo/pull/1"),
+            campaign_id: String::from("hacktoberfest-2023"),
+            labels: vec![String::from("Hacktoberfest-Accepted")],
+            merged_at: Some(at("2023-10-12T00:00:00Z")),
+            approved: true,
+            created_at: Some(at("2023-10-10T00:00:00Z")),
+            spam_score: Some(10),
+            ..Default::default()
+        }
+    }
+
+    fn rule(json: &str) -> Predicate {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn all_reports_every_failing_predicate() {
+        let predicate =
+            rule(r#"{"all": [{"merged": true}, {"approved": false}, {"checks_passed": true}]}"#);
+        assert_eq!(
+            predicate.evaluate(&merged_pull()),
+            Err(vec![
+                String::from("approved is false"),
+                String::from("checks passed is true")
+            ])
+        );
+    }
+
+    #[test]
+    fn any_passes_on_the_first_holding_predicate() {
+        let predicate =
+            rule(r#"{"any": [{"label": "invalid"}, {"label": "hacktoberfest-accepted"}]}"#);
+        assert_eq!(predicate.evaluate(&merged_pull()), Ok(()));
+
+        let predicate = rule(r#"{"any": [{"label": "invalid"}, {"merged": false}]}"#);
+        assert_eq!(
+            predicate.evaluate(&merged_pull()),
+            Err(vec![
+                String::from("label invalid"),
+                String::from("merged is false")
+            ])
+        );
+    }
+
+    #[test]
+    fn not_blames_itself() {
+        let predicate = rule(r#"{"not": {"any_label": ["spam", "hacktoberfest-accepted"]}}"#);
+        assert_eq!(
+            predicate.evaluate(&merged_pull()),
+            Err(vec![String::from(
+                "not(any label of spam, hacktoberfest-accepted)"
+            )])
+        );
+    }
+
+    #[test]
+    fn dates_need_a_stored_time() {
+        let facts = merged_pull();
+        assert!(rule(r#"{"created_after": "2023-10-01T00:00:00Z"}"#)
+            .evaluate(&facts)
+            .is_ok());
+        assert!(rule(r#"{"merged_before": "2023-10-12T00:00:00Z"}"#)
+            .evaluate(&facts)
+            .is_err());
+
+        let unmerged = EligibilityFacts {
+            merged_at: None,
+            ..merged_pull()
+        };
+        assert!(rule(r#"{"merged_after": "2023-10-01T00:00:00Z"}"#)
+            .evaluate(&unmerged)
+            .is_err());
+    }
+
+    #[test]
+    fn unscored_items_fail_the_spam_check() {
+        let predicate = rule(r#"{"spam_score_below": 50}"#);
+        assert!(predicate.evaluate(&merged_pull()).is_ok());
+        assert!(predicate
+            .evaluate(&EligibilityFacts {
+                spam_score: None,
+                ..merged_pull()
+            })
+            .is_err());
+        assert!(predicate
+            .evaluate(&EligibilityFacts {
+                spam_score: Some(50),
+                ..merged_pull()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn bot_authors_fail_the_default_exclusion() {
+        let predicate = Predicate::Not(Box::new(Predicate::AuthorIsBot(true)));
+        assert!(predicate.evaluate(&merged_pull()).is_ok());
+        assert!(predicate
+            .evaluate(&EligibilityFacts {
+                author_is_bot: true,
+                ..merged_pull()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn rules_cover_their_campaign_only() {
+        let mut rule = EligibilityRule {
+            name: String::from("merged"),
+            applies_to: ItemKind::PullRequest,
+            campaign_id: Some(String::from("other")),
+            predicate: Predicate::Merged(true),
+        };
+        assert!(!rule.covers(&merged_pull()));
+
+        rule.campaign_id = None;
+        assert!(rule.covers(&merged_pull()));
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::{DateTime, Utc};
+
+use crate::contributors::GHOST_LOGIN;
+use crate::issues_tracker_local::{github_http_post_gql, graphql_error, GraphQLError};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssignmentEvent {
+    pub event_id: String, // node id of the timeline item
+    pub issue_id: String, // url of the issue
+    pub assignee: String,
+    pub actor: String, // GHOST_LOGIN when the account was deleted
+    pub event: String, // assigned or unassigned
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn get_issue_assignment_events(issue_url: &str) -> anyhow::Result<Vec<AssignmentEvent>> {
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct GraphQLResponse {
+        data: Option<Data>,
+        errors: Option<Vec<GraphQLError>>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Data {
+        resource: Option<Resource>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Resource {
+        timelineItems: Option<TimelineItems>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct TimelineItems {
+        nodes: Option<Vec<TimelineEvent>>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct TimelineEvent {
+        __typename: String,
+        id: String,
+        actor: Option<Author>,
+        assignee: Option<Author>,
+        createdAt: DateTime<Utc>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Author {
+        login: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct PageInfo {
+        endCursor: Option<String>,
+        hasNextPage: bool,
+    }
+
+    let mut all_events = Vec::new();
+    let mut after_cursor: Option<String> = None;
+
+    loop {
+        let query_str = format!(
+            r#"
+            query {{
+                resource(url: "{}") {{
+                    ... on Issue {{
+                        timelineItems(first: 100, after: {}, itemTypes: [ASSIGNED_EVENT, UNASSIGNED_EVENT]) {{
+                            nodes {{
+                                __typename
+                                ... on AssignedEvent {{
+                                    id
+                                    actor {{
+                                        login
+                                    }}
+                                    assignee {{
+                                        ... on Actor {{
+                                            login
+                                        }}
+                                    }}
+                                    createdAt
+                                }}
+                                ... on UnassignedEvent {{
+                                    id
+                                    actor {{
+                                        login
+                                    }}
+                                    assignee {{
+                                        ... on Actor {{
+                                            login
+                                        }}
+                                    }}
+                                    createdAt
+                                }}
+                            }}
+                            pageInfo {{
+                                endCursor
+                                hasNextPage
+                            }}
+                        }}
+                    }}
+                }}
+            }}
+            "#,
+            issue_url,
+            after_cursor
+                .as_ref()
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+        );
+
+        let response_body = github_http_post_gql(&query_str)
+            .await
+            .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+        let response: GraphQLResponse = serde_json::from_slice(&response_body)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+        if response.errors.is_some() {
+            return Err(graphql_error(&response.errors));
+        }
+        let timeline_items = response
+            .data
+            .and_then(|data| data.resource)
+            .and_then(|resource| resource.timelineItems)
+            .ok_or_else(|| anyhow!("no timeline found for {}", issue_url))?;
+
+        for event in timeline_items.nodes.unwrap_or_default() {
+            let kind = match event.__typename.as_str() {
+                "AssignedEvent" => "assigned",
+                "UnassignedEvent" => "unassigned",
+                _ => continue,
+            };
+            // the assignee of an event is gone when the account was deleted
+            let assignee = match event.assignee.and_then(|assignee| assignee.login) {
+                Some(login) => login,
+                None => continue,
+            };
+
+            all_events.push(AssignmentEvent {
+                event_id: event.id,
+                issue_id: issue_url.to_string(),
+                assignee,
+                actor: event
+                    .actor
+                    .and_then(|actor| actor.login)
+                    .unwrap_or_else(|| GHOST_LOGIN.to_string()),
+                event: kind.to_string(),
+                created_at: event.createdAt,
+            });
+        }
+
+        match timeline_items.pageInfo {
+            PageInfo {
+                hasNextPage: true,
+                endCursor: Some(cursor),
+            } => after_cursor = Some(cursor),
+            _ => break,
+        }
+    }
+
+    Ok(all_events)
+}
+
+// replays the events up to `at`, holders are returned in the order they were assigned
+pub fn assignees_at(events: &[AssignmentEvent], at: DateTime<Utc>) -> Vec<String> {
+    let mut ordered = events
+        .iter()
+        .filter(|event| event.created_at <= at)
+        .collect::<Vec<&AssignmentEvent>>();
+    ordered.sort_by_key(|event| event.created_at);
+
+    let mut holders: Vec<String> = Vec::new();
+    for event in ordered {
+        match event.event.as_str() {
+            "assigned" if !holders.contains(&event.assignee) => {
+                holders.push(event.assignee.clone());
+            }
+            "unassigned" => holders.retain(|holder| holder != &event.assignee),
+            _ => {}
+        }
+    }
+
+    holders
+}
+
+pub fn current_assignees(events: &[AssignmentEvent]) -> Vec<String> {
+    assignees_at(events, Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(assignee: &str, kind: &str, day: u32) -> AssignmentEvent {
+        AssignmentEvent {
+            event_id: format!("{kind}-{assignee}-{day}"),
+            issue_id: String::from("https://github.com/owner/repo/issues/1"),
+            assignee: assignee.to_string(),
+            actor: String::from("maintainer"),
+            event: kind.to_string(),
+            created_at: Utc.with_ymd_and_hms(2023, 10, day, 12, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn replays_events_in_time_order() {
+        // stored out of order on purpose
+        let events = vec![
+            event("bob", "assigned", 3),
+            event("alice", "assigned", 1),
+            event("alice", "unassigned", 2),
+        ];
+        assert_eq!(current_assignees(&events), vec![String::from("bob")]);
+    }
+
+    #[test]
+    fn holders_keep_the_order_they_were_assigned_in() {
+        let events = vec![
+            event("alice", "assigned", 1),
+            event("bob", "assigned", 2),
+            event("alice", "assigned", 3),
+        ];
+        assert_eq!(
+            current_assignees(&events),
+            vec![String::from("alice"), String::from("bob")]
+        );
+    }
+
+    #[test]
+    fn ignores_events_after_the_moment_asked_for() {
+        let events = vec![
+            event("alice", "assigned", 1),
+            event("alice", "unassigned", 5),
+        ];
+        let at = Utc.with_ymd_and_hms(2023, 10, 3, 0, 0, 0).unwrap();
+        assert_eq!(assignees_at(&events, at), vec![String::from("alice")]);
+        assert!(current_assignees(&events).is_empty());
+    }
+}
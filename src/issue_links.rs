@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "link_origin", rename_all = "snake_case")]
+pub enum LinkOrigin {
+    Keyword,        // "Fixes #123" in the pull_request, reported by closingIssuesReferences
+    Connected,      // linked by hand in the sidebar, reported as a CONNECTED_EVENT
+    CrossReference, // the pull_request mentioned the issue, see issue_references
+    Mention,        // found in text without a closing keyword, see reference_parser
+}
+
+// the same name the database stores
+impl fmt::Display for LinkOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LinkOrigin::Keyword => "keyword",
+            LinkOrigin::Connected => "connected",
+            LinkOrigin::CrossReference => "cross_reference",
+            LinkOrigin::Mention => "mention",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PullIssueLink {
+    pub pull_id: String,  // url of the pull_request
+    pub issue_id: String, // url of the issue
+    pub origin: LinkOrigin,
+}
+
+// CONNECTED_EVENT and DISCONNECTED_EVENT items of a pull_request's timeline, by issue url
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connected(String),
+    Disconnected(String),
+}
+
+impl ConnectionEvent {
+    pub fn from_typename(typename: &str, issue_id: String) -> Self {
+        match typename {
+            "DisconnectedEvent" => ConnectionEvent::Disconnected(issue_id),
+            _ => ConnectionEvent::Connected(issue_id),
+        }
+    }
+}
+
+// the issues still connected once the events are replayed oldest first, a sidebar link that
+// was removed again is gone
+pub fn connected_issues(events: &[ConnectionEvent]) -> Vec<String> {
+    let mut connected: Vec<String> = Vec::new();
+    for event in events {
+        match event {
+            ConnectionEvent::Connected(issue_id) if !connected.contains(issue_id) => {
+                connected.push(issue_id.clone());
+            }
+            ConnectionEvent::Disconnected(issue_id) => connected.retain(|id| id != issue_id),
+            _ => {}
+        }
+    }
+    connected
+}
+
+// closingIssuesReferences lists the manually connected issues as well, so only the ones
+// without a CONNECTED_EVENT are put down to closing keywords
+pub fn merge_pull_links(
+    pull_id: &str,
+    connected_issues: &[String],
+    closing_issues: &[String],
+) -> Vec<PullIssueLink> {
+    let mut links: Vec<PullIssueLink> = Vec::new();
+    let connected = connected_issues
+        .iter()
+        .map(|issue_id| (issue_id, LinkOrigin::Connected));
+    let closing = closing_issues
+        .iter()
+        .filter(|issue_id| !connected_issues.contains(issue_id))
+        .map(|issue_id| (issue_id, LinkOrigin::Keyword));
+
+    for (issue_id, origin) in connected.chain(closing) {
+        let link = PullIssueLink {
+            pull_id: pull_id.to_string(),
+            issue_id: issue_id.clone(),
+            origin,
+        };
+        if !links.contains(&link) {
+            links.push(link);
+        }
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_the_stored_name() {
+        assert_eq!(LinkOrigin::Keyword.to_string(), "keyword");
+        assert_eq!(LinkOrigin::CrossReference.to_string(), "cross_reference");
+    }
+
+    fn issues(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn connected_issues_are_not_put_down_to_keywords() {
+        let links = merge_pull_links(
+            "https://github.com/o/r/pull/1",
+            &issues(&["https://github.com/o/r/issues/2"]),
+            &issues(&[
+                "https://github.com/o/r/issues/2",
+                "https://github.com/o/r/issues/3",
+            ]),
+        );
+
+        assert_eq!(
+            links
+                .iter()
+                .map(|link| (link.issue_id.as_str(), link.origin))
+                .collect::<Vec<_>>(),
+            vec![
+                ("https://github.com/o/r/issues/2", LinkOrigin::Connected),
+                ("https://github.com/o/r/issues/3", LinkOrigin::Keyword),
+            ]
+        );
+        assert!(links
+            .iter()
+            .all(|link| link.pull_id == "https://github.com/o/r/pull/1"));
+    }
+
+    #[test]
+    fn repeated_issues_are_linked_once() {
+        let links = merge_pull_links(
+            "https://github.com/o/r/pull/1",
+            &issues(&[
+                "https://github.com/o/r/issues/2",
+                "https://github.com/o/r/issues/2",
+            ]),
+            &issues(&[
+                "https://github.com/o/r/issues/3",
+                "https://github.com/o/r/issues/3",
+            ]),
+        );
+        assert_eq!(links.len(), 2);
+        assert!(merge_pull_links("https://github.com/o/r/pull/1", &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn disconnected_issues_are_dropped() {
+        let events = [
+            ConnectionEvent::from_typename("ConnectedEvent", String::from("a")),
+            ConnectionEvent::from_typename("ConnectedEvent", String::from("b")),
+            ConnectionEvent::from_typename("DisconnectedEvent", String::from("a")),
+            ConnectionEvent::from_typename("ConnectedEvent", String::from("b")),
+        ];
+        assert_eq!(connected_issues(&events), issues(&["b"]));
+
+        let reconnected = [
+            ConnectionEvent::Connected(String::from("a")),
+            ConnectionEvent::Disconnected(String::from("a")),
+            ConnectionEvent::Connected(String::from("a")),
+        ];
+        assert_eq!(connected_issues(&reconnected), issues(&["a"]));
+    }
+}
@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+
+use crate::contributors::GHOST_LOGIN;
+use crate::issues_tracker_local::{github_http_post_gql, graphql_error, GraphQLError};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IssueReference {
+    pub issue_id: String,          // url of the referenced issue
+    pub source_id: String,         // url of the referencing pull_request, issue or commit
+    pub source_type: String,       // PullRequest, Issue or Commit
+    pub source_state: String,      // OPEN, CLOSED, MERGED, empty for commits
+    pub source_repository: String, // url of the repository the reference came from
+    pub actor: String,
+    pub is_cross_repository: bool,
+    pub will_close_target: bool,
+    pub referenced_at: DateTime<Utc>,
+}
+
+// collects CrossReferencedEvent and ReferencedEvent items from the timeline of an issue, events
+// without a timestamp are left out
+pub async fn get_issue_cross_references(issue_url: &str) -> anyhow::Result<Vec<IssueReference>> {
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct GraphQLResponse {
+        data: Option<Data>,
+        errors: Option<Vec<GraphQLError>>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Data {
+        resource: Option<Resource>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Resource {
+        timelineItems: Option<TimelineItems>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct TimelineItems {
+        nodes: Option<Vec<TimelineEvent>>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct TimelineEvent {
+        __typename: String,
+        actor: Option<Author>,
+        // CrossReferencedEvent
+        source: Option<Source>,
+        referencedAt: Option<DateTime<Utc>>,
+        willCloseTarget: Option<bool>,
+        // ReferencedEvent
+        commit: Option<Commit>,
+        commitRepository: Option<Repository>,
+        createdAt: Option<DateTime<Utc>>,
+        isCrossRepository: Option<bool>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Author {
+        login: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Source {
+        __typename: String,
+        url: Option<String>,
+        state: Option<String>,
+        repository: Option<Repository>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Commit {
+        url: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Repository {
+        url: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct PageInfo {
+        endCursor: Option<String>,
+        hasNextPage: bool,
+    }
+
+    let mut all_references = Vec::new();
+    let mut after_cursor: Option<String> = None;
+
+    loop {
+        let query_str = format!(
+            r#"
+            query {{
+                resource(url: "{}") {{
+                    ... on Issue {{
+                        timelineItems(first: 100, after: {}, itemTypes: [CROSS_REFERENCED_EVENT, REFERENCED_EVENT]) {{
+                            nodes {{
+                                __typename
+                                ... on CrossReferencedEvent {{
+                                    actor {{
+                                        login
+                                    }}
+                                    source {{
+                                        __typename
+                                        ... on PullRequest {{
+                                            url
+                                            state
+                                            repository {{
+                                                url
+                                            }}
+                                        }}
+                                        ... on Issue {{
+                                            url
+                                            state
+                                            repository {{
+                                                url
+                                            }}
+                                        }}
+                                    }}
+                                    referencedAt
+                                    isCrossRepository
+                                    willCloseTarget
+                                }}
+                                ... on ReferencedEvent {{
+                                    actor {{
+                                        login
+                                    }}
+                                    commit {{
+                                        url
+                                    }}
+                                    commitRepository {{
+                                        url
+                                    }}
+                                    createdAt
+                                    isCrossRepository
+                                }}
+                            }}
+                            pageInfo {{
+                                endCursor
+                                hasNextPage
+                            }}
+                        }}
+                    }}
+                }}
+            }}
+            "#,
+            issue_url,
+            after_cursor
+                .as_ref()
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+        );
+
+        let response_body = github_http_post_gql(&query_str)
+            .await
+            .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+        let response: GraphQLResponse = serde_json::from_slice(&response_body)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+        if response.errors.is_some() {
+            return Err(graphql_error(&response.errors));
+        }
+        let timeline_items = response
+            .data
+            .and_then(|data| data.resource)
+            .and_then(|resource| resource.timelineItems)
+            .ok_or_else(|| anyhow!("no timeline found for {}", issue_url))?;
+
+        for event in timeline_items.nodes.unwrap_or_default() {
+            let actor = event
+                .actor
+                .and_then(|actor| actor.login)
+                .unwrap_or_else(|| GHOST_LOGIN.to_string());
+            let is_cross_repository = event.isCrossRepository.unwrap_or(false);
+
+            let reference = match event.__typename.as_str() {
+                "CrossReferencedEvent" => {
+                    let (Some(source), Some(referenced_at)) = (event.source, event.referencedAt)
+                    else {
+                        continue;
+                    };
+                    IssueReference {
+                        issue_id: issue_url.to_string(),
+                        source_id: source.url.unwrap_or_default(),
+                        source_type: source.__typename,
+                        source_state: source.state.unwrap_or_default(),
+                        source_repository: source
+                            .repository
+                            .and_then(|repo| repo.url)
+                            .unwrap_or_default(),
+                        actor,
+                        is_cross_repository,
+                        will_close_target: event.willCloseTarget.unwrap_or(false),
+                        referenced_at,
+                    }
+                }
+                "ReferencedEvent" => {
+                    let Some(referenced_at) = event.createdAt else {
+                        continue;
+                    };
+                    IssueReference {
+                        issue_id: issue_url.to_string(),
+                        source_id: event
+                            .commit
+                            .and_then(|commit| commit.url)
+                            .unwrap_or_default(),
+                        source_type: String::from("Commit"),
+                        source_state: String::new(),
+                        source_repository: event
+                            .commitRepository
+                            .and_then(|repo| repo.url)
+                            .unwrap_or_default(),
+                        actor,
+                        is_cross_repository,
+                        will_close_target: false,
+                        referenced_at,
+                    }
+                }
+                _ => continue,
+            };
+
+            // sources we are not allowed to see come back without a url
+            if !reference.source_id.is_empty() {
+                all_references.push(reference);
+            }
+        }
+
+        match timeline_items.pageInfo {
+            PageInfo {
+                hasNextPage: true,
+                endCursor: Some(cursor),
+            } => after_cursor = Some(cursor),
+            _ => break,
+        }
+    }
+
+    Ok(all_references)
+}
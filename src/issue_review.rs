@@ -0,0 +1,11 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(sqlx::Type, ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[sqlx(type_name = "review_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewStatus {
+    Queue,   // waiting for a maintainer
+    Approve, // the issue and its budget are accepted for the campaign
+    Decline,
+}
@@ -1,36 +1,41 @@
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{DateTime, Utc};
 
 use anyhow::anyhow;
-use octocrab::{models::issues::Issue, Octocrab};
-use std::env;
 
-use http_req::{
-    request::{Method, Request},
-    uri::Uri,
+use crate::bots::bot_login;
+use crate::contributors::GHOST_LOGIN;
+use crate::issues_tracker_local::{
+    get_issue_comments, github_http_post_gql, graphql_error, GraphQLError, IssueComment,
 };
+use crate::repository_metadata::escape;
 use serde::{Deserialize, Serialize};
-use std::io::Write;
-
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct OuterIssue {
+    pub node_id: String, // GraphQL id, the same issue found by two windows is written once
     pub title: String,
     pub url: String,
+    pub updated_at: DateTime<Utc>, // drives the sync watermark
     pub author: String,
     pub body: String,
     pub repository: String,
     pub repository_stars: i64,
     pub issue_labels: Vec<String>,
-    pub comments: Vec<String>, // Concat of author and comment
+    pub comments: Vec<IssueComment>,
     pub close_reason: String,
     pub close_pull_request: String,
     pub close_author: String,
 }
 
-pub async fn search_issues_closed(query: &str) -> anyhow::Result<Vec<OuterIssue>> {
+// one page of up to 100 results and the cursor of the next one, None after the last page
+pub async fn search_issues_closed_page(
+    query: &str,
+    after_cursor: Option<&str>,
+) -> anyhow::Result<(Vec<OuterIssue>, Option<String>)> {
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct GraphQLResponse {
         data: Option<Data>,
+        errors: Option<Vec<GraphQLError>>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -58,9 +63,11 @@ pub async fn search_issues_closed(query: &str) -> anyhow::Result<Vec<OuterIssue>
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Issue {
+        id: String,
         title: Option<String>,
         url: Option<String>,
         body: Option<String>,
+        updatedAt: DateTime<Utc>,
         author: Option<Author>,
         repository: Option<Repository>,
         labels: Option<Labels>,
@@ -70,6 +77,7 @@ pub async fn search_issues_closed(query: &str) -> anyhow::Result<Vec<OuterIssue>
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Author {
+        __typename: Option<String>,
         login: Option<String>,
     }
 
@@ -102,6 +110,7 @@ pub async fn search_issues_closed(query: &str) -> anyhow::Result<Vec<OuterIssue>
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Comments {
         edges: Option<Vec<CommentEdge>>,
+        pageInfo: PageInfo,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -111,8 +120,12 @@ pub async fn search_issues_closed(query: &str) -> anyhow::Result<Vec<OuterIssue>
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Comment {
+        databaseId: i64,
+        url: String,
         author: Option<Author>,
         body: Option<String>,
+        createdAt: DateTime<Utc>,
+        updatedAt: DateTime<Utc>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -138,68 +151,71 @@ pub async fn search_issues_closed(query: &str) -> anyhow::Result<Vec<OuterIssue>
         author: Option<Author>,
     }
 
-    let first_comments = 10;
-    let first_timeline_items = 10;
     let mut all_issues = Vec::new();
-    let mut after_cursor: Option<String> = None;
-    let file_path = "issues.txt";
-    let mut file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(file_path)?;
-    let mut count = 0;
+    let mut next_cursor = None;
 
-    for _ in 0..10 {
-        let query_str = format!(
-            r#"
-            query {{
-                search(query: "{}", type: ISSUE, first: 100, after: {}) {{
-                    issueCount
-                    edges {{
-                        node {{
-                            ... on Issue {{
-                                title
+    let query_str = format!(
+        r#"
+        query {{
+            search(query: "{}", type: ISSUE, first: 100, after: {}) {{
+                issueCount
+                edges {{
+                    node {{
+                        ... on Issue {{
+                            id
+                            title
+                            url
+                            body
+                            updatedAt
+                            author {{
+                                __typename
+                                login
+                            }}
+                            repository {{
                                 url
-                                body
-                                author {{
-                                    login
+                                stargazers {{
+                                    totalCount
                                 }}
-                                repository {{
-                                    url
-                                    stargazers {{
-                                        totalCount
+                            }}
+                            labels(first: 10) {{
+                                edges {{
+                                    node {{
+                                        name
                                     }}
                                 }}
-                                labels(first: 10) {{
-                                    edges {{
-                                        node {{
-                                            name
+                            }}
+                            comments(first: 100) {{
+                                edges {{
+                                    node {{
+                                        databaseId
+                                        url
+                                        author {{
+                                            __typename
+                                            login
                                         }}
+                                        body
+                                        createdAt
+                                        updatedAt
                                     }}
                                 }}
-                                comments(first: 10) {{
-                                    edges {{
-                                        node {{
-                                            author {{
-                                                login
-                                            }}
-                                            body
-                                        }}
-                                    }}
+                                pageInfo {{
+                                    endCursor
+                                    hasNextPage
                                 }}
-                                timelineItems(first: 10, itemTypes: [CLOSED_EVENT]) {{
-                                    edges {{
-                                        node {{
-                                            ... on ClosedEvent {{
-                                                stateReason
-                                                closer {{
-                                                    __typename
-                                                    ... on PullRequest {{
-                                                        title
-                                                        url
-                                                        author {{
-                                                            login
-                                                        }}
+                            }}
+                            timelineItems(first: 10, itemTypes: [CLOSED_EVENT]) {{
+                                edges {{
+                                    node {{
+                                        ... on ClosedEvent {{
+                                            stateReason
+                                            closer {{
+                                                __typename
+                                                ... on PullRequest {{
+                                                    title
+                                                    url
+                                                    author {{
+                                                        __typename
+                                                        login
                                                     }}
                                                 }}
                                             }}
@@ -209,142 +225,160 @@ pub async fn search_issues_closed(query: &str) -> anyhow::Result<Vec<OuterIssue>
                             }}
                         }}
                     }}
-                    pageInfo {{
-                        endCursor
-                        hasNextPage
-                    }}
+                }}
+                pageInfo {{
+                    endCursor
+                    hasNextPage
                 }}
             }}
-            "#,
-            query.replace("\"", "\\\""),
-            after_cursor
-                .as_ref()
-                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
-        );
-
-        let response_body = github_http_post_gql(&query_str)
-            .await
-            .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
-
-        let response: GraphQLResponse = serde_json::from_slice(&response_body)
-            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
-
-        if let Some(data) = response.data {
-            if let Some(search) = data.search {
-                for edge in search.edges.unwrap_or_default() {
-                    if let Some(issue) = edge.node {
-                        let labels = issue.labels.map_or(Vec::new(), |labels| {
-                            labels.edges.map_or(Vec::new(), |edges| {
-                                edges
-                                    .iter()
-                                    .filter_map(|edge| {
-                                        edge.node
-                                            .as_ref()
-                                            .map(|label| label.name.clone().unwrap_or_default())
-                                    })
-                                    .collect()
-                            })
-                        });
-let temp_str   = String::from("");
-
-                        let comments = issue.comments.map_or(Vec::new(), |comments| {
-                            comments.edges.map_or(Vec::new(), |edges| {
-                                edges
-                                    .iter()
-                                    .filter_map(|edge| {
-                                        edge.node.as_ref().map(|comment| {
-                                            format!(
-                                                "{}: {}",
-                                                comment.author.as_ref().map_or("", |a| a
-                                                    .login
-                                                    .as_ref()
-                                                    .unwrap_or(&temp_str)),
-                                                comment.body.as_ref().unwrap_or(&"".to_string())
-                                            )
-                                        })
-                                    })
-                                    .collect()
+        }}
+        "#,
+        escape(query),
+        after_cursor
+            .as_ref()
+            .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+    );
+
+    let response_body = github_http_post_gql(&query_str)
+        .await
+        .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+    let response: GraphQLResponse = serde_json::from_slice(&response_body)
+        .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+    if response.errors.is_some() {
+        return Err(graphql_error(&response.errors));
+    }
+    // a page without a search is a failed request, not the end of the results
+    let search = response
+        .data
+        .and_then(|data| data.search)
+        .ok_or_else(|| anyhow!("no search results for {}", query))?;
+
+    for edge in search.edges.unwrap_or_default() {
+        if let Some(issue) = edge.node {
+            let labels = issue.labels.map_or(Vec::new(), |labels| {
+                labels.edges.map_or(Vec::new(), |edges| {
+                    edges
+                        .iter()
+                        .filter_map(|edge| {
+                            edge.node
+                                .as_ref()
+                                .map(|label| label.name.clone().unwrap_or_default())
+                        })
+                        .collect()
+                })
+            });
+
+            let comments = match issue.comments {
+                Some(comments) if comments.pageInfo.hasNextPage => {
+                    get_issue_comments(issue.url.as_deref().unwrap_or_default()).await?
+                }
+                Some(comments) => comments
+                    .edges
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|edge| edge.node)
+                    .map(|comment| IssueComment {
+                        comment_id: comment.url,
+                        database_id: comment.databaseId,
+                        author: comment
+                            .author
+                            .and_then(|author| {
+                                Some(bot_login(author.login?, author.__typename.as_deref()))
                             })
-                        });
-
-                        let (close_reason, close_pull_request, close_author) = issue
-                            .timelineItems
-                            .map_or((String::new(), String::new(), String::new()), |items| {
-                                items.edges.map_or(
-                                    (String::new(), String::new(), String::new()),
-                                    |edges| {
-                                        edges
-                                            .iter()
-                                            .filter_map(|edge| {
-                                                edge.node.as_ref().map(|event| {
-                                                    if let Some(closer) = &event.closer {
-                                                        (
-                                                            event
-                                                                .stateReason
-                                                                .clone()
-                                                                .unwrap_or_default(),
-                                                            closer
-                                                                .url
+                            .unwrap_or_else(|| GHOST_LOGIN.to_string()),
+                        body: comment.body.unwrap_or_default(),
+                        created_at: comment.createdAt,
+                        updated_at: comment.updatedAt,
+                    })
+                    .collect(),
+                // left out of the search, an empty list would mark the stored ones deleted
+                None => get_issue_comments(issue.url.as_deref().unwrap_or_default()).await?,
+            };
+
+            let (close_reason, close_pull_request, close_author) = issue.timelineItems.map_or(
+                (String::new(), String::new(), String::new()),
+                |items| {
+                    items
+                        .edges
+                        .map_or((String::new(), String::new(), String::new()), |edges| {
+                            edges
+                                .iter()
+                                .filter_map(|edge| {
+                                    edge.node.as_ref().map(|event| {
+                                        if let Some(closer) = &event.closer {
+                                            (
+                                                event.stateReason.clone().unwrap_or_default(),
+                                                closer.url.clone().unwrap_or_default(),
+                                                closer.author.as_ref().map_or(
+                                                    String::new(),
+                                                    |author| {
+                                                        bot_login(
+                                                            author
+                                                                .login
                                                                 .clone()
                                                                 .unwrap_or_default(),
-                                                            closer.author.as_ref().map_or(
-                                                                String::new(),
-                                                                |author| {
-                                                                    author
-                                                                        .login
-                                                                        .clone()
-                                                                        .unwrap_or_default()
-                                                                },
-                                                            ),
+                                                            author.__typename.as_deref(),
                                                         )
-                                                    } else {
-                                                        (
-                                                            String::new(),
-                                                            String::new(),
-                                                            String::new(),
-                                                        )
-                                                    }
-                                                })
-                                            })
-                                            .next()
-                                            .unwrap_or((
-                                                String::new(),
-                                                String::new(),
-                                                String::new(),
-                                            ))
-                                    },
-                                )
-                            });
-
-                        all_issues.push(OuterIssue {
-                            title: issue.title.unwrap_or_default(),
-                            url: issue.url.unwrap_or_default(),
-                            author: issue
-                                .author
-                                .map_or(String::new(), |author| author.login.unwrap_or_default()),
-                            body: issue.body.unwrap_or_default(),
-                            repository: issue
-                                .repository
-                                .clone() // Clone here
-                                .map_or(String::new(), |repo| repo.url.unwrap_or_default()),
-                            repository_stars: issue.repository.map_or(0, |repo| {
-                                repo.stargazers
-                                    .map_or(0, |stars| stars.totalCount.unwrap_or(0))
-                            }),
-                            issue_labels: labels,
-                            comments: comments,
-                            close_reason: close_reason,
-                            close_pull_request: close_pull_request,
-                            close_author: close_author,
-                        });
-                    }
-                }
-                if search.pageInfo.hasNextPage {
-                    after_cursor = search.pageInfo.endCursor
-                } else {
-                    break;
-                }
-            }
+                                                    },
+                                                ),
+                                            )
+                                        } else {
+                                            (String::new(), String::new(), String::new())
+                                        }
+                                    })
+                                })
+                                .next()
+                                .unwrap_or((String::new(), String::new(), String::new()))
+                        })
+                },
+            );
+
+            all_issues.push(OuterIssue {
+                node_id: issue.id,
+                title: issue.title.unwrap_or_default(),
+                url: issue.url.unwrap_or_default(),
+                updated_at: issue.updatedAt,
+                author: issue
+                    .author
+                    .and_then(|author| Some(bot_login(author.login?, author.__typename.as_deref())))
+                    .unwrap_or_default(),
+                body: issue.body.unwrap_or_default(),
+                repository: issue
+                    .repository
+                    .as_ref()
+                    .and_then(|repo| repo.url.clone())
+                    .unwrap_or_default(),
+                repository_stars: issue
+                    .repository
+                    .as_ref()
+                    .and_then(|repo| repo.stargazers.as_ref())
+                    .and_then(|stars| stars.totalCount)
+                    .unwrap_or(0),
+                issue_labels: labels,
+                comments,
+                close_reason,
+                close_pull_request,
+                close_author,
+            });
+        }
+    }
+    if search.pageInfo.hasNextPage {
+        next_cursor = search.pageInfo.endCursor;
+    }
+    Ok((all_issues, next_cursor))
+}
+
+pub async fn search_issues_closed(query: &str) -> anyhow::Result<Vec<OuterIssue>> {
+    let mut all_issues = Vec::new();
+    let mut after_cursor: Option<String> = None;
+    for _ in 0..10 {
+        let (page, next_cursor) = search_issues_closed_page(query, after_cursor.as_deref()).await?;
+        all_issues.extend(page);
+        match next_cursor {
+            Some(cursor) => after_cursor = Some(cursor),
+            None => break,
         }
     }
     Ok(all_issues)
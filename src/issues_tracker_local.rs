@@ -1,15 +1,14 @@
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 
+use crate::bots::bot_login;
+use crate::contributors::GHOST_LOGIN;
+use crate::repository_metadata::escape;
 use anyhow::anyhow;
-use octocrab::{models::issues::Issue, Octocrab};
 use std::env;
 
-use http_req::{
-    request::{Method, Request},
-    uri::Uri,
-};
 use serde::{Deserialize, Serialize};
-use std::io::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::OnceLock;
 
 pub fn inner_query_by_date_range(
     start_date: &str,
@@ -53,61 +52,131 @@ pub fn inner_query_by_date_range(
     out
 }
 
-pub async fn github_http_post_gql(query: &str) -> anyhow::Result<Vec<u8>> {
-    let token = env::var("GITHUB_TOKEN").expect("github_token is required");
-    let base_url = "https://api.github.com/graphql";
-    let base_url = Uri::try_from(base_url).unwrap();
-    let mut writer = Vec::new();
+const GITHUB_GRAPHQL_URL: &str = "https://api.github.com/graphql";
 
-    let query = serde_json::json!({"query": query});
-    match Request::new(&base_url)
-        .method(Method::POST)
-        .header("User-Agent", "flows-network connector")
+// x-ratelimit-remaining of the latest GitHub response, -1 until one arrived
+static RATE_LIMIT_REMAINING: AtomicI64 = AtomicI64::new(-1);
+
+// one client for the process, concurrent requests share its connection pool
+fn github_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent("flows-network connector")
+            .build()
+            .expect("failed to build the http client")
+    })
+}
+
+// the GitHub rate limit points left as of the latest response, None before the first request
+pub fn rate_limit_remaining() -> Option<i64> {
+    match RATE_LIMIT_REMAINING.load(Ordering::Relaxed) {
+        -1 => None,
+        remaining => Some(remaining),
+    }
+}
+
+async fn github_send(request: reqwest::RequestBuilder) -> anyhow::Result<Vec<u8>> {
+    let token = env::var("GITHUB_TOKEN").map_err(|_| anyhow!("GITHUB_TOKEN is required"))?;
+
+    let response = match request
         .header("Content-Type", "application/json")
-        .header("Authorization", &format!("Bearer {}", token))
-        .header("Content-Length", &query.to_string().len())
-        .body(&query.to_string().into_bytes())
-        .send(&mut writer)
+        .bearer_auth(token)
+        .send()
+        .await
     {
-        Ok(res) => {
-            if !res.status_code().is_success() {
-                log::error!("Github http error {:?}", res.status_code());
-                return Err(anyhow::anyhow!("Github http error {:?}", res.status_code()));
-            }
-            Ok(writer)
-        }
+        Ok(response) => response,
         Err(_e) => {
             log::error!("Error getting response from Github: {:?}", _e);
-            Err(anyhow::anyhow!(_e))
+            return Err(anyhow::anyhow!(_e));
         }
+    };
+
+    if let Some(remaining) = response
+        .headers()
+        .get("x-ratelimit-remaining")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i64>().ok())
+    {
+        RATE_LIMIT_REMAINING.store(remaining, Ordering::Relaxed);
+    }
+
+    if !response.status().is_success() {
+        log::error!("Github http error {:?}", response.status());
+        return Err(anyhow::anyhow!("Github http error {:?}", response.status()));
     }
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+// GitHub answers a failed query with 200 and `errors`, `data` is then null or partial
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GraphQLError {
+    pub message: String,
+    #[serde(rename = "type", default)]
+    pub kind: Option<String>, // e.g. NOT_FOUND, FORBIDDEN, RATE_LIMITED
+    #[serde(default)]
+    pub path: Vec<serde_json::Value>, // the alias and fields the error belongs to
+}
+
+impl GraphQLError {
+    // a looked-up node that doesn't exist, anything else means the answer can't be trusted
+    pub fn is_not_found(&self) -> bool {
+        self.kind.as_deref() == Some("NOT_FOUND")
+    }
+}
+
+pub fn graphql_error(errors: &Option<Vec<GraphQLError>>) -> anyhow::Error {
+    let messages = errors
+        .iter()
+        .flatten()
+        .map(|error| error.message.as_str())
+        .collect::<Vec<&str>>();
+    if messages.is_empty() {
+        anyhow!("GitHub GraphQL response has no data")
+    } else {
+        anyhow!("GitHub GraphQL error: {}", messages.join("; "))
+    }
+}
+
+pub async fn github_http_post_gql(query: &str) -> anyhow::Result<Vec<u8>> {
+    let query = serde_json::json!({"query": query});
+
+    github_send(
+        github_client()
+            .post(GITHUB_GRAPHQL_URL)
+            .body(query.to_string()),
+    )
+    .await
 }
 
 pub async fn github_http_get(url: &str) -> anyhow::Result<Vec<u8>> {
-    let token = std::env::var("GITHUB_TOKEN").expect("github_token is required");
-    let mut writer = Vec::new();
-    let url = Uri::try_from(url).unwrap();
+    github_send(github_client().get(url)).await
+}
 
-    match Request::new(&url)
-        .method(Method::GET)
-        .header("User-Agent", "flows-network connector")
-        .header("Content-Type", "application/json")
-        .header("Authorization", &format!("Bearer {}", token))
-        .header("CONNECTION", "close")
-        .send(&mut writer)
-    {
-        Ok(res) => {
-            if !res.status_code().is_success() {
-                println!("Github http error {:?}", res.status_code());
-                return Err(anyhow::anyhow!("Github http error {:?}", res.status_code()));
-            }
-            Ok(writer)
-        }
-        Err(_e) => {
-            println!("Error getting response from Github: {:?}", _e);
-            Err(anyhow::anyhow!(_e))
-        }
+// points left in the GraphQL rate limit window, asked for explicitly so a sync can size itself
+// before its first request
+pub async fn get_rate_limit_remaining() -> anyhow::Result<i64> {
+    #[derive(Serialize, Deserialize, Debug)]
+    struct GraphQLResponse {
+        data: Data,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Data {
+        rateLimit: RateLimit,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct RateLimit {
+        remaining: i64,
     }
+
+    let response_body = github_http_post_gql("query { rateLimit { remaining } }").await?;
+    let response: GraphQLResponse = serde_json::from_slice(&response_body)
+        .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+    Ok(response.data.rateLimit.remaining)
 }
 
 pub async fn get_project_logo(owner: &str, repo: &str) -> anyhow::Result<String> {
@@ -156,23 +225,177 @@ pub async fn get_project_logo(owner: &str, repo: &str) -> anyhow::Result<String>
     let owner_info = parsed_response.data.repository.owner;
     Ok(owner_info.avatarUrl)
 }
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IssueComment {
+    pub comment_id: String, // url of the comment
+    pub database_id: i64,
+    pub author: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// issue_url can point at an issue or a pull request, all pages are fetched; any page that
+// fails fails the whole list, callers treat the list as complete
+pub async fn get_issue_comments(issue_url: &str) -> anyhow::Result<Vec<IssueComment>> {
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct GraphQLResponse {
+        data: Option<Data>,
+        errors: Option<Vec<GraphQLError>>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Data {
+        resource: Option<Resource>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Resource {
+        comments: Option<Comments>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Comments {
+        nodes: Option<Vec<Comment>>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Comment {
+        databaseId: i64,
+        url: String,
+        author: Option<Author>,
+        body: Option<String>,
+        createdAt: DateTime<Utc>,
+        updatedAt: DateTime<Utc>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Author {
+        __typename: Option<String>,
+        login: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct PageInfo {
+        endCursor: Option<String>,
+        hasNextPage: bool,
+    }
+
+    let mut all_comments = Vec::new();
+    let mut after_cursor: Option<String> = None;
+
+    loop {
+        let query_str = format!(
+            r#"
+            query {{
+                resource(url: "{}") {{
+                    ... on Issue {{
+                        comments(first: 100, after: {}) {{
+                            ...commentFields
+                        }}
+                    }}
+                    ... on PullRequest {{
+                        comments(first: 100, after: {}) {{
+                            ...commentFields
+                        }}
+                    }}
+                }}
+            }}
+
+            fragment commentFields on IssueCommentConnection {{
+                nodes {{
+                    databaseId
+                    url
+                    author {{
+                        __typename
+                        login
+                    }}
+                    body
+                    createdAt
+                    updatedAt
+                }}
+                pageInfo {{
+                    endCursor
+                    hasNextPage
+                }}
+            }}
+            "#,
+            issue_url,
+            after_cursor
+                .as_ref()
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+            after_cursor
+                .as_ref()
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+        );
+
+        let response_body = github_http_post_gql(&query_str)
+            .await
+            .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+        let response: GraphQLResponse = serde_json::from_slice(&response_body)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+        if response.errors.is_some() {
+            return Err(graphql_error(&response.errors));
+        }
+        let comments = response
+            .data
+            .and_then(|data| data.resource)
+            .and_then(|resource| resource.comments)
+            .ok_or_else(|| anyhow!("no comments found for {}", issue_url))?;
+
+        for comment in comments.nodes.unwrap_or_default() {
+            all_comments.push(IssueComment {
+                comment_id: comment.url,
+                database_id: comment.databaseId,
+                author: comment
+                    .author
+                    .and_then(|author| Some(bot_login(author.login?, author.__typename.as_deref())))
+                    .unwrap_or_else(|| GHOST_LOGIN.to_string()),
+                body: comment.body.unwrap_or_default(),
+                created_at: comment.createdAt,
+                updated_at: comment.updatedAt,
+            });
+        }
+
+        match comments.pageInfo {
+            PageInfo {
+                hasNextPage: true,
+                endCursor: Some(cursor),
+            } => after_cursor = Some(cursor),
+            _ => break,
+        }
+    }
+
+    Ok(all_comments)
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct OuterIssue {
+    pub node_id: String, // GraphQL id, the same issue found by two windows is written once
     pub title: String,
     pub url: String,
+    pub updated_at: DateTime<Utc>, // drives the sync watermark
     pub author: String,
     pub body: String,
     pub repository: String,
     pub repository_stars: i64,
     pub repository_avatar: String,
     pub issue_labels: Vec<String>,
-    pub comments: Vec<String>,
+    pub comments: Vec<IssueComment>,
 }
 
-pub async fn search_issues_open(query: &str) -> anyhow::Result<Vec<OuterIssue>> {
+// one page of up to 100 results and the cursor of the next one, None after the last page
+pub async fn search_issues_open_page(
+    query: &str,
+    after_cursor: Option<&str>,
+) -> anyhow::Result<(Vec<OuterIssue>, Option<String>)> {
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct GraphQLResponse {
         data: Option<Data>,
+        errors: Option<Vec<GraphQLError>>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -200,9 +423,11 @@ pub async fn search_issues_open(query: &str) -> anyhow::Result<Vec<OuterIssue>>
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Issue {
+        id: String,
         title: Option<String>,
         url: Option<String>,
         body: Option<String>,
+        updatedAt: DateTime<Utc>,
         author: Option<Author>,
         repository: Option<Repository>,
         labels: Option<Labels>,
@@ -211,6 +436,7 @@ pub async fn search_issues_open(query: &str) -> anyhow::Result<Vec<OuterIssue>>
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Author {
+        __typename: Option<String>,
         login: Option<String>,
     }
 
@@ -249,6 +475,7 @@ pub async fn search_issues_open(query: &str) -> anyhow::Result<Vec<OuterIssue>>
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Comments {
         edges: Option<Vec<CommentEdge>>,
+        pageInfo: PageInfo,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -258,156 +485,193 @@ pub async fn search_issues_open(query: &str) -> anyhow::Result<Vec<OuterIssue>>
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Comment {
+        databaseId: i64,
+        url: String,
         author: Option<Author>,
         body: Option<String>,
+        createdAt: DateTime<Utc>,
+        updatedAt: DateTime<Utc>,
     }
 
-    let first_comments = 10;
-    let first_timeline_items = 10;
     let mut all_issues = Vec::new();
-    let mut after_cursor: Option<String> = None;
-    let file_path = "issues.txt";
-    let mut file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(file_path)?;
-    let mut count = 0;
+    let mut next_cursor = None;
 
-    for _ in 0..10 {
-        let query_str = format!(
-            r#"
-            query {{
-                search(query: "{}", type: ISSUE, first: 100, after: {}) {{
-                    issueCount
-                    edges {{
-                        node {{
-                            ... on Issue {{
-                                title
+    let query_str = format!(
+        r#"
+        query {{
+            search(query: "{}", type: ISSUE, first: 100, after: {}) {{
+                issueCount
+                edges {{
+                    node {{
+                        ... on Issue {{
+                            id
+                            title
+                            url
+                            body
+                            updatedAt
+                            author {{
+                                __typename
+                                login
+                            }}
+                            repository {{
                                 url
-                                body
-                                author {{
-                                    login
+                                stargazers {{
+                                    totalCount
                                 }}
-                                repository {{
-                                    url
-                                    stargazers {{
-                                        totalCount
-                                    }}
-                                    owner {{
-                                        avatarUrl
-                                    }}
+                                owner {{
+                                    avatarUrl
                                 }}
-                                labels(first: 10) {{
-                                    edges {{
-                                        node {{
-                                            name
-                                        }}
+                            }}
+                            labels(first: 10) {{
+                                edges {{
+                                    node {{
+                                        name
                                     }}
                                 }}
-                                comments(first: 10) {{
-                                    edges {{
-                                        node {{
-                                            author {{
-                                                login
-                                            }}
-                                            body
+                            }}
+                            comments(first: 100) {{
+                                edges {{
+                                    node {{
+                                        databaseId
+                                        url
+                                        author {{
+                                            __typename
+                                            login
                                         }}
+                                        body
+                                        createdAt
+                                        updatedAt
                                     }}
                                 }}
+                                pageInfo {{
+                                    endCursor
+                                    hasNextPage
+                                }}
                             }}
                         }}
                     }}
-                    pageInfo {{
-                        endCursor
-                        hasNextPage
-                    }}
+                }}
+                pageInfo {{
+                    endCursor
+                    hasNextPage
                 }}
             }}
-            "#,
-            query.replace("\"", "\\\""),
-            after_cursor
-                .as_ref()
-                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
-        );
+        }}
+        "#,
+        escape(query),
+        after_cursor
+            .as_ref()
+            .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+    );
 
-        let response_body = github_http_post_gql(&query_str)
-            .await
-            .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+    let response_body = github_http_post_gql(&query_str)
+        .await
+        .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
 
-        let response: GraphQLResponse = serde_json::from_slice(&response_body)
-            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+    let response: GraphQLResponse = serde_json::from_slice(&response_body)
+        .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
 
-        if let Some(data) = response.data {
-            if let Some(search) = data.search {
-                for edge in search.edges.unwrap_or_default() {
-                    if let Some(issue) = edge.node {
-                        let labels = issue.labels.map_or(Vec::new(), |labels| {
-                            labels.edges.map_or(Vec::new(), |edges| {
-                                edges
-                                    .iter()
-                                    .filter_map(|edge| {
-                                        edge.node
-                                            .as_ref()
-                                            .map(|label| label.name.clone().unwrap_or_default())
-                                    })
-                                    .collect()
-                            })
-                        });
-                        let temp_str = String::from("");
-                        let comments = issue.comments.map_or(Vec::new(), |comments| {
-                            comments.edges.map_or(Vec::new(), |edges| {
-                                edges
-                                    .iter()
-                                    .filter_map(|edge| {
-                                        edge.node.as_ref().map(|comment| {
-                                            format!(
-                                                "{}: {}",
-                                                comment.author.as_ref().map_or("", |a| a
-                                                    .login
-                                                    .as_ref()
-                                                    .unwrap_or(&temp_str)),
-                                                comment.body.as_ref().unwrap_or(&"".to_string())
-                                            )
-                                        })
-                                    })
-                                    .collect()
-                            })
-                        });
-
-                        all_issues.push(OuterIssue {
-                            title: issue.title.unwrap_or_default(),
-                            url: issue.url.unwrap_or_default(),
-                            author: issue
-                                .author.clone()
-                                .map_or(String::new(), |author| author.login.unwrap_or_default()),
-                            body: issue.body.clone().unwrap_or_default(),
-                            repository: issue
-                                .repository
-                                .clone() // Clone here
-                                .map_or(String::new(), |repo| repo.url.unwrap_or_default()),
-                            repository_stars: issue.repository.clone().map_or(0, |repo| {
-                                repo.stargazers
-                                    .map_or(0, |stars| stars.totalCount.unwrap_or(0))
-                            }),
-                            repository_avatar: issue
-                                .repository
-                                .map_or(String::new(), |repo| {
-                                    repo.owner
-                                        .map_or(String::new(), |owner| owner.avatarUrl.unwrap_or_default())
-                                }),
-                            issue_labels: labels,
-                            comments: comments,
-                        });
-                    }
-                }
-                if search.pageInfo.hasNextPage {
-                    after_cursor = search.pageInfo.endCursor
-                } else {
-                    break;
+    if response.errors.is_some() {
+        return Err(graphql_error(&response.errors));
+    }
+    // a page without a search is a failed request, not the end of the results
+    let search = response
+        .data
+        .and_then(|data| data.search)
+        .ok_or_else(|| anyhow!("no search results for {}", query))?;
+
+    for edge in search.edges.unwrap_or_default() {
+        if let Some(issue) = edge.node {
+            let labels = issue.labels.map_or(Vec::new(), |labels| {
+                labels.edges.map_or(Vec::new(), |edges| {
+                    edges
+                        .iter()
+                        .filter_map(|edge| {
+                            edge.node
+                                .as_ref()
+                                .map(|label| label.name.clone().unwrap_or_default())
+                        })
+                        .collect()
+                })
+            });
+            let comments = match issue.comments {
+                // the first page came with the search, fetch the whole list again
+                // only for the busy issues
+                Some(comments) if comments.pageInfo.hasNextPage => {
+                    get_issue_comments(issue.url.as_deref().unwrap_or_default()).await?
                 }
-            }
+                Some(comments) => comments
+                    .edges
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|edge| edge.node)
+                    .map(|comment| IssueComment {
+                        comment_id: comment.url,
+                        database_id: comment.databaseId,
+                        author: comment
+                            .author
+                            .and_then(|author| {
+                                Some(bot_login(author.login?, author.__typename.as_deref()))
+                            })
+                            .unwrap_or_else(|| GHOST_LOGIN.to_string()),
+                        body: comment.body.unwrap_or_default(),
+                        created_at: comment.createdAt,
+                        updated_at: comment.updatedAt,
+                    })
+                    .collect(),
+                // left out of the search, an empty list would mark the stored ones deleted
+                None => get_issue_comments(issue.url.as_deref().unwrap_or_default()).await?,
+            };
+
+            all_issues.push(OuterIssue {
+                node_id: issue.id,
+                title: issue.title.unwrap_or_default(),
+                url: issue.url.unwrap_or_default(),
+                updated_at: issue.updatedAt,
+                author: issue
+                    .author
+                    .clone()
+                    .and_then(|author| Some(bot_login(author.login?, author.__typename.as_deref())))
+                    .unwrap_or_default(),
+                body: issue.body.clone().unwrap_or_default(),
+                repository: issue
+                    .repository
+                    .as_ref()
+                    .and_then(|repo| repo.url.clone())
+                    .unwrap_or_default(),
+                repository_stars: issue
+                    .repository
+                    .as_ref()
+                    .and_then(|repo| repo.stargazers.as_ref())
+                    .and_then(|stars| stars.totalCount)
+                    .unwrap_or(0),
+                repository_avatar: issue
+                    .repository
+                    .and_then(|repo| repo.owner)
+                    .and_then(|owner| owner.avatarUrl)
+                    .unwrap_or_default(),
+                issue_labels: labels,
+                comments,
+            });
         }
     }
+    if search.pageInfo.hasNextPage {
+        next_cursor = search.pageInfo.endCursor;
+    }
 
+    Ok((all_issues, next_cursor))
+}
+
+pub async fn search_issues_open(query: &str) -> anyhow::Result<Vec<OuterIssue>> {
+    let mut all_issues = Vec::new();
+    let mut after_cursor: Option<String> = None;
+    for _ in 0..10 {
+        let (page, next_cursor) = search_issues_open_page(query, after_cursor.as_deref()).await?;
+        all_issues.extend(page);
+        match next_cursor {
+            Some(cursor) => after_cursor = Some(cursor),
+            None => break,
+        }
+    }
     Ok(all_issues)
 }
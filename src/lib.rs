@@ -1,2 +1,31 @@
+// the GraphQL response structs keep GitHub's camelCase field names so serde maps them as is
+#![allow(non_snake_case)]
+
+pub mod bots;
+pub mod campaign_config;
+pub mod campaigns;
+pub mod cli;
+pub mod contributors;
+pub mod db_ops;
 pub mod db_updater_local;
+pub mod dry_run;
+pub mod eligibility;
+pub mod issue_assignments;
+pub mod issue_links;
+pub mod issue_references;
+pub mod issue_review;
+pub mod issue_search_closed;
 pub mod issues_tracker_local;
+pub mod listing;
+pub mod output;
+pub mod pull_checks;
+pub mod pull_files;
+pub mod pull_request_overall_search;
+pub mod pull_request_per_repo_search;
+pub mod pull_reviews;
+pub mod reference_parser;
+pub mod repository_metadata;
+pub mod review_queue;
+pub mod spam_score;
+pub mod stale_claims;
+pub mod sync_runs;
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+
+use crate::issue_review::ReviewStatus;
+use crate::output::Row;
+use clap::Args;
+use serde_json::{json, Value};
+
+#[derive(Clone, Debug)]
+pub struct ProjectRow {
+    pub project_id: String,
+    pub project_logo: String,
+    pub issue_count: i64,
+    pub opted_in: Option<bool>, // None until the repo metadata was checked
+}
+
+impl Row for ProjectRow {
+    const COLUMNS: &'static [&'static str] = &["project_id", "project_logo", "issues", "opted_in"];
+    const DEFAULT_COLUMNS: &'static [&'static str] = &["project_id", "issues", "opted_in"];
+
+    fn cell(&self, column: &str) -> Value {
+        match column {
+            "project_id" => json!(self.project_id),
+            "project_logo" => json!(self.project_logo),
+            "issues" => json!(self.issue_count),
+            "opted_in" => json!(self.opted_in),
+            _ => Value::Null,
+        }
+    }
+}
+
+// effort adds up the merged pull_requests linked to the issue
+#[derive(Clone, Debug)]
+pub struct IssueRow {
+    pub issue_id: String,
+    pub project_id: String,
+    pub title: String,
+    pub description: String,
+    pub author: Option<String>,
+    pub assignee: Option<String>,
+    pub budget: Option<i32>,
+    pub budget_approved: Option<bool>,
+    pub status: Option<String>,
+    pub review_status: Option<ReviewStatus>,
+    pub linked_pr: Option<String>,
+    pub pulls: i64,
+    pub additions: i64,
+    pub deletions: i64,
+    pub changed_files: i64,
+    pub commits: i64,
+}
+
+impl Row for IssueRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "issue_id",
+        "project_id",
+        "title",
+        "description",
+        "author",
+        "assignee",
+        "budget",
+        "budget_approved",
+        "status",
+        "review_status",
+        "linked_pr",
+        "pulls",
+        "additions",
+        "deletions",
+        "changed_files",
+        "commits",
+    ];
+    const DEFAULT_COLUMNS: &'static [&'static str] = &[
+        "issue_id",
+        "title",
+        "assignee",
+        "budget",
+        "status",
+        "review_status",
+        "pulls",
+    ];
+
+    fn cell(&self, column: &str) -> Value {
+        match column {
+            "issue_id" => json!(self.issue_id),
+            "project_id" => json!(self.project_id),
+            "title" => json!(self.title),
+            "description" => json!(self.description),
+            "author" => json!(self.author),
+            "assignee" => json!(self.assignee),
+            "budget" => json!(self.budget),
+            "budget_approved" => json!(self.budget_approved),
+            "status" => json!(self.status),
+            "review_status" => json!(self.review_status),
+            "linked_pr" => json!(self.linked_pr),
+            "pulls" => json!(self.pulls),
+            "additions" => json!(self.additions),
+            "deletions" => json!(self.deletions),
+            "changed_files" => json!(self.changed_files),
+            "commits" => json!(self.commits),
+            _ => Value::Null,
+        }
+    }
+}
+
+// every filter left empty matches all issues, set ones must all match
+#[derive(Args, Clone, Debug, Default)]
+pub struct IssueFilter {
+    #[arg(long, help = "Only issues with this status, e.g. open or closed")]
+    pub status: Option<String>,
+    #[arg(long, value_enum)]
+    pub review_status: Option<ReviewStatus>,
+    #[arg(long, help = "Only issues with at least this budget")]
+    pub min_budget: Option<i32>,
+    #[arg(long, help = "Only issues with at most this budget")]
+    pub max_budget: Option<i32>,
+    #[arg(long)]
+    pub assignee: Option<String>,
+    #[arg(long, help = "Only issues of this project url")]
+    pub project: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PullRequestRow {
+    pub pull_id: String,
+    pub title: String,
+    pub author: Option<String>, // None when the account was deleted
+    pub repository: String,
+    pub merged_by: Option<String>,
+    pub labels: Vec<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub additions: Option<i32>,
+    pub deletions: Option<i32>,
+    pub spam_score: Option<i32>,
+}
+
+impl Row for PullRequestRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "pull_id",
+        "title",
+        "author",
+        "repository",
+        "merged_by",
+        "labels",
+        "created_at",
+        "merged_at",
+        "additions",
+        "deletions",
+        "spam_score",
+    ];
+    const DEFAULT_COLUMNS: &'static [&'static str] =
+        &["pull_id", "title", "author", "merged_by", "spam_score"];
+
+    fn cell(&self, column: &str) -> Value {
+        match column {
+            "pull_id" => json!(self.pull_id),
+            "title" => json!(self.title),
+            "author" => json!(self.author),
+            "repository" => json!(self.repository),
+            "merged_by" => json!(self.merged_by),
+            "labels" => json!(self.labels),
+            "created_at" => json!(self.created_at),
+            "merged_at" => json!(self.merged_at),
+            "additions" => json!(self.additions),
+            "deletions" => json!(self.deletions),
+            "spam_score" => json!(self.spam_score),
+            _ => Value::Null,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CommentRow {
+    pub comment_id: String,
+    pub issue_id: String,
+    pub creator: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub content: String,
+}
+
+impl Row for CommentRow {
+    const COLUMNS: &'static [&'static str] =
+        &["comment_id", "issue_id", "creator", "created_at", "content"];
+    const DEFAULT_COLUMNS: &'static [&'static str] = &["creator", "created_at", "content"];
+
+    fn cell(&self, column: &str) -> Value {
+        match column {
+            "comment_id" => json!(self.comment_id),
+            "issue_id" => json!(self.issue_id),
+            "creator" => json!(self.creator),
+            "created_at" => json!(self.created_at),
+            "content" => json!(self.content),
+            _ => Value::Null,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LeaderboardRow {
+    pub author: String,
+    pub merged: i64,
+    pub lines: i64, // additions plus deletions over the merged pull_requests
+}
+
+impl Row for LeaderboardRow {
+    const COLUMNS: &'static [&'static str] = &["author", "merged", "lines"];
+    const DEFAULT_COLUMNS: &'static [&'static str] = &["author", "merged", "lines"];
+
+    fn cell(&self, column: &str) -> Value {
+        match column {
+            "author" => json!(self.author),
+            "merged" => json!(self.merged),
+            "lines" => json!(self.lines),
+            _ => Value::Null,
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use anyhow::anyhow;
+use clap::ValueEnum;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::io::Write;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table, // aligned columns for reading in a terminal
+    Jsonl, // one json object per line
+    Csv,
+}
+
+// a typed row the listing commands can render, cells are json values so numbers sort as numbers
+// and jsonl keeps their type
+pub trait Row {
+    // every column, in display order
+    const COLUMNS: &'static [&'static str];
+    // what table and csv show when no columns are asked for, jsonl always gets every column
+    const DEFAULT_COLUMNS: &'static [&'static str];
+
+    fn cell(&self, column: &str) -> Value;
+}
+
+fn unknown_column<R: Row>(column: &str) -> anyhow::Error {
+    anyhow!(
+        "unknown column {}, expected one of {}",
+        column,
+        R::COLUMNS.join(", ")
+    )
+}
+
+// checks the requested columns against the row type, an empty request picks the format's default
+pub fn select_columns<R: Row>(
+    requested: &[String],
+    format: OutputFormat,
+) -> anyhow::Result<Vec<&'static str>> {
+    if requested.is_empty() {
+        return Ok(match format {
+            OutputFormat::Jsonl => R::COLUMNS.to_vec(),
+            OutputFormat::Table | OutputFormat::Csv => R::DEFAULT_COLUMNS.to_vec(),
+        });
+    }
+
+    requested
+        .iter()
+        .map(|column| {
+            R::COLUMNS
+                .iter()
+                .find(|known| known.eq_ignore_ascii_case(column.trim()))
+                .copied()
+                .ok_or_else(|| unknown_column::<R>(column))
+        })
+        .collect()
+}
+
+// nulls first, then numbers by value and everything else by its text
+fn compare_cells(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        _ => cell_text(a).cmp(&cell_text(b)),
+    }
+}
+
+// stable, so rows equal in `column` keep the order the query returned them in
+pub fn sort_rows<R: Row>(rows: &mut [R], column: &str, descending: bool) -> anyhow::Result<()> {
+    let column = R::COLUMNS
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(column.trim()))
+        .ok_or_else(|| unknown_column::<R>(column))?;
+
+    rows.sort_by(|a, b| {
+        let ordering = compare_cells(&a.cell(column), &b.cell(column));
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    Ok(())
+}
+
+// the text shown in a table or csv cell, lists are joined with commas
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(cell_text)
+            .collect::<Vec<String>>()
+            .join(","),
+        other => other.to_string(),
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) || text.trim() != text {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn write_table<R: Row>(out: &mut impl Write, rows: &[R], columns: &[&str]) -> anyhow::Result<()> {
+    // a table row stays on one line whatever the cell holds
+    let cells = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| cell_text(&row.cell(column)).replace(['\n', '\r', '\t'], " "))
+                .collect::<Vec<String>>()
+        })
+        .collect::<Vec<Vec<String>>>();
+
+    let widths = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<usize>>();
+
+    let line = |values: &[String]| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{value:<width$}"))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let header = columns
+        .iter()
+        .map(|column| column.to_uppercase())
+        .collect::<Vec<String>>();
+    writeln!(out, "{}", line(&header))?;
+    for row in &cells {
+        writeln!(out, "{}", line(row))?;
+    }
+    Ok(())
+}
+
+fn write_jsonl<R: Row>(out: &mut impl Write, rows: &[R], columns: &[&str]) -> anyhow::Result<()> {
+    // built by hand, a serde_json map would sort the keys instead of keeping the column order
+    for row in rows {
+        let fields = columns
+            .iter()
+            .map(|column| -> anyhow::Result<String> {
+                Ok(format!(
+                    "{}:{}",
+                    serde_json::to_string(column)?,
+                    serde_json::to_string(&row.cell(column))?
+                ))
+            })
+            .collect::<anyhow::Result<Vec<String>>>()?;
+        writeln!(out, "{{{}}}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+fn write_csv<R: Row>(out: &mut impl Write, rows: &[R], columns: &[&str]) -> anyhow::Result<()> {
+    writeln!(out, "{}", columns.join(","))?;
+    for row in rows {
+        let fields = columns
+            .iter()
+            .map(|column| csv_field(&cell_text(&row.cell(column))))
+            .collect::<Vec<String>>();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+pub fn render<R: Row>(
+    out: &mut impl Write,
+    rows: &[R],
+    columns: &[&str],
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => write_table(out, rows, columns),
+        OutputFormat::Jsonl => write_jsonl(out, rows, columns),
+        OutputFormat::Csv => write_csv(out, rows, columns),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Item {
+        name: &'static str,
+        count: Option<i64>,
+        tags: Vec<&'static str>,
+    }
+
+    impl Row for Item {
+        const COLUMNS: &'static [&'static str] = &["name", "count", "tags"];
+        const DEFAULT_COLUMNS: &'static [&'static str] = &["name", "count"];
+
+        fn cell(&self, column: &str) -> Value {
+            match column {
+                "name" => json!(self.name),
+                "count" => json!(self.count),
+                "tags" => json!(self.tags),
+                _ => Value::Null,
+            }
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item {
+                name: "b, \"quoted\"",
+                count: Some(10),
+                tags: vec!["x", "y"],
+            },
+            Item {
+                name: "a\nline",
+                count: None,
+                tags: vec![],
+            },
+            Item {
+                name: "c",
+                count: Some(9),
+                tags: vec!["z"],
+            },
+        ]
+    }
+
+    fn rendered(rows: &[Item], columns: &[&str], format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        render(&mut out, rows, columns, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn default_columns_depend_on_the_format() {
+        assert_eq!(
+            select_columns::<Item>(&[], OutputFormat::Table).unwrap(),
+            vec!["name", "count"]
+        );
+        assert_eq!(
+            select_columns::<Item>(&[], OutputFormat::Jsonl).unwrap(),
+            vec!["name", "count", "tags"]
+        );
+    }
+
+    #[test]
+    fn requested_columns_are_matched_case_insensitively() {
+        let requested = vec![String::from(" Tags"), String::from("NAME")];
+        assert_eq!(
+            select_columns::<Item>(&requested, OutputFormat::Csv).unwrap(),
+            vec!["tags", "name"]
+        );
+
+        let unknown = select_columns::<Item>(&[String::from("size")], OutputFormat::Csv);
+        assert_eq!(
+            unknown.unwrap_err().to_string(),
+            "unknown column size, expected one of name, count, tags"
+        );
+    }
+
+    #[test]
+    fn sorts_numbers_by_value_with_nulls_first() {
+        let mut rows = items();
+        sort_rows(&mut rows, "count", false).unwrap();
+        assert_eq!(
+            rows.iter().map(|row| row.count).collect::<Vec<_>>(),
+            vec![None, Some(9), Some(10)]
+        );
+
+        sort_rows(&mut rows, "Count", true).unwrap();
+        assert_eq!(
+            rows.iter().map(|row| row.count).collect::<Vec<_>>(),
+            vec![Some(10), Some(9), None]
+        );
+    }
+
+    #[test]
+    fn table_keeps_each_row_on_one_line() {
+        assert_eq!(
+            rendered(&items(), &["name", "count", "tags"], OutputFormat::Table),
+            "NAME         COUNT  TAGS\n\
+             b, \"quoted\"  10     x,y\n\
+             a line\n\
+             c            9      z\n"
+        );
+    }
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        assert_eq!(
+            rendered(&items(), &["name", "count", "tags"], OutputFormat::Csv),
+            "name,count,tags\n\
+             \"b, \"\"quoted\"\"\",10,\"x,y\"\n\
+             \"a\nline\",,\n\
+             c,9,z\n"
+        );
+    }
+
+    #[test]
+    fn jsonl_keeps_the_column_order_and_types() {
+        assert_eq!(
+            rendered(
+                &items()[2..],
+                &["tags", "count", "name"],
+                OutputFormat::Jsonl
+            ),
+            "{\"tags\":[\"z\"],\"count\":9,\"name\":\"c\"}\n"
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::issues_tracker_local::{github_http_post_gql, graphql_error, GraphQLError};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+// conclusions GitHub itself accepts for a required check
+pub const PASSING_CHECK_STATES: [&str; 3] = ["SUCCESS", "NEUTRAL", "SKIPPED"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PullCheck {
+    pub name: String,
+    pub kind: String, // CheckRun or StatusContext
+    // conclusion of a completed CheckRun, its status otherwise, or the StatusContext state
+    pub state: String,
+    pub is_required: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PullChecks {
+    pub commit_url: String, // merge commit, or the head commit when the merge commit has no checks
+    pub rollup_state: String, // SUCCESS, FAILURE, PENDING, ERROR or EXPECTED
+    pub checks: Vec<PullCheck>,
+}
+
+// checks of the merge commit of a pull_request; None when neither the merge commit nor the head
+// commit reports any status, an error when GitHub didn't answer so a failed fetch never passes
+pub async fn get_pull_request_checks(pull_url: &str) -> anyhow::Result<Option<PullChecks>> {
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct CommitResponse {
+        data: Option<CommitData>,
+        errors: Option<Vec<GraphQLError>>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct CommitData {
+        resource: Option<PullRequest>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct PullRequest {
+        number: i64,
+        mergeCommit: Option<Commit>,
+        commits: Option<Commits>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Commits {
+        nodes: Vec<CommitNode>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct CommitNode {
+        commit: Commit,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Commit {
+        url: String,
+        statusCheckRollup: Option<Rollup>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Rollup {
+        state: String,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct GraphQLResponse {
+        data: Option<Data>,
+        errors: Option<Vec<GraphQLError>>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Data {
+        resource: Option<Resource>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Resource {
+        statusCheckRollup: Option<RollupContexts>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct RollupContexts {
+        contexts: Contexts,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Contexts {
+        nodes: Vec<Context>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Context {
+        __typename: String,
+        // CheckRun
+        name: Option<String>,
+        status: Option<String>,
+        conclusion: Option<String>,
+        // StatusContext
+        context: Option<String>,
+        state: Option<String>,
+        isRequired: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct PageInfo {
+        endCursor: Option<String>,
+        hasNextPage: bool,
+    }
+
+    let query_str = format!(
+        r#"
+        query {{
+            resource(url: "{pull_url}") {{
+                ... on PullRequest {{
+                    number
+                    mergeCommit {{
+                        url
+                        statusCheckRollup {{
+                            state
+                        }}
+                    }}
+                    commits(last: 1) {{
+                        nodes {{
+                            commit {{
+                                url
+                                statusCheckRollup {{
+                                    state
+                                }}
+                            }}
+                        }}
+                    }}
+                }}
+            }}
+        }}
+        "#,
+    );
+
+    let response_body = github_http_post_gql(&query_str)
+        .await
+        .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+    let response: CommitResponse = serde_json::from_slice(&response_body)
+        .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+    if response.errors.is_some() {
+        return Err(graphql_error(&response.errors));
+    }
+    let pull = response
+        .data
+        .and_then(|data| data.resource)
+        .ok_or_else(|| anyhow!("no pull request found for {}", pull_url))?;
+
+    // merge commits on the default branch often run no checks of their own, the checks that
+    // gated the merge are the ones on the head commit
+    let head_commit = pull
+        .commits
+        .and_then(|commits| commits.nodes.into_iter().next())
+        .map(|node| node.commit);
+    let (commit_url, rollup_state) = match (pull.mergeCommit, head_commit) {
+        (
+            Some(Commit {
+                url,
+                statusCheckRollup: Some(rollup),
+            }),
+            _,
+        ) => (url, rollup.state),
+        (
+            _,
+            Some(Commit {
+                url,
+                statusCheckRollup: Some(rollup),
+            }),
+        ) => (url, rollup.state),
+        _ => return Ok(None),
+    };
+
+    let mut checks = Vec::new();
+    let mut after_cursor: Option<String> = None;
+
+    loop {
+        let query_str = format!(
+            r#"
+            query {{
+                resource(url: "{}") {{
+                    ... on Commit {{
+                        statusCheckRollup {{
+                            contexts(first: 100, after: {}) {{
+                                nodes {{
+                                    __typename
+                                    ... on CheckRun {{
+                                        name
+                                        status
+                                        conclusion
+                                        isRequired(pullRequestNumber: {})
+                                    }}
+                                    ... on StatusContext {{
+                                        context
+                                        state
+                                        isRequired(pullRequestNumber: {})
+                                    }}
+                                }}
+                                pageInfo {{
+                                    endCursor
+                                    hasNextPage
+                                }}
+                            }}
+                        }}
+                    }}
+                }}
+            }}
+            "#,
+            commit_url,
+            after_cursor
+                .as_ref()
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+            pull.number,
+            pull.number,
+        );
+
+        let response_body = github_http_post_gql(&query_str)
+            .await
+            .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+        let response: GraphQLResponse = serde_json::from_slice(&response_body)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+        if response.errors.is_some() {
+            return Err(graphql_error(&response.errors));
+        }
+        let contexts = response
+            .data
+            .and_then(|data| data.resource)
+            .and_then(|resource| resource.statusCheckRollup)
+            .map(|rollup| rollup.contexts)
+            .ok_or_else(|| anyhow!("no checks found for {}", commit_url))?;
+
+        for context in contexts.nodes {
+            let check = match context.__typename.as_str() {
+                "CheckRun" => PullCheck {
+                    name: context.name.unwrap_or_default(),
+                    kind: String::from("CheckRun"),
+                    state: context.conclusion.or(context.status).unwrap_or_default(),
+                    is_required: context.isRequired,
+                },
+                "StatusContext" => PullCheck {
+                    name: context.context.unwrap_or_default(),
+                    kind: String::from("StatusContext"),
+                    state: context.state.unwrap_or_default(),
+                    is_required: context.isRequired,
+                },
+                _ => continue,
+            };
+            checks.push(check);
+        }
+
+        match contexts.pageInfo {
+            PageInfo {
+                hasNextPage: true,
+                endCursor: Some(cursor),
+            } => after_cursor = Some(cursor),
+            _ => break,
+        }
+    }
+
+    Ok(Some(PullChecks {
+        commit_url,
+        rollup_state,
+        checks,
+    }))
+}
+
+// repos without branch protection mark nothing as required, then every reported check has to pass;
+// no stored checks at all means nothing was verified, which never counts as passing
+pub fn all_required_checks_succeeded(checks: &[PullCheck]) -> bool {
+    let passing = |check: &PullCheck| PASSING_CHECK_STATES.contains(&check.state.as_str());
+
+    if checks.is_empty() {
+        false
+    } else if checks.iter().any(|check| check.is_required) {
+        checks.iter().filter(|check| check.is_required).all(passing)
+    } else {
+        checks.iter().all(passing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(state: &str, is_required: bool) -> PullCheck {
+        PullCheck {
+            name: String::from("ci"),
+            kind: String::from("CheckRun"),
+            state: state.to_string(),
+            is_required,
+        }
+    }
+
+    #[test]
+    fn only_required_checks_count_when_some_are_required() {
+        assert!(all_required_checks_succeeded(&[
+            check("SUCCESS", true),
+            check("FAILURE", false)
+        ]));
+        assert!(!all_required_checks_succeeded(&[
+            check("PENDING", true),
+            check("SUCCESS", false)
+        ]));
+    }
+
+    #[test]
+    fn every_check_counts_without_branch_protection() {
+        assert!(all_required_checks_succeeded(&[
+            check("SUCCESS", false),
+            check("SKIPPED", false)
+        ]));
+        assert!(!all_required_checks_succeeded(&[
+            check("SUCCESS", false),
+            check("FAILURE", false)
+        ]));
+    }
+
+    #[test]
+    fn no_checks_is_not_a_pass() {
+        assert!(!all_required_checks_succeeded(&[]));
+    }
+}
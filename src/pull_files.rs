@@ -0,0 +1,112 @@
+use crate::issues_tracker_local::github_http_post_gql;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PullFile {
+    pub path: String,
+    pub additions: i32,
+    pub deletions: i32,
+}
+
+// every changed file of a pull_request, GitHub stops listing them at 3000
+pub async fn get_pull_request_files(pull_url: &str) -> anyhow::Result<Vec<PullFile>> {
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct GraphQLResponse {
+        data: Option<Data>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Data {
+        resource: Option<Resource>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Resource {
+        files: Option<Files>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Files {
+        nodes: Option<Vec<File>>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct File {
+        path: String,
+        additions: i32,
+        deletions: i32,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct PageInfo {
+        endCursor: Option<String>,
+        hasNextPage: bool,
+    }
+
+    let mut all_files = Vec::new();
+    let mut after_cursor: Option<String> = None;
+
+    loop {
+        let query_str = format!(
+            r#"
+            query {{
+                resource(url: "{}") {{
+                    ... on PullRequest {{
+                        files(first: 100, after: {}) {{
+                            nodes {{
+                                path
+                                additions
+                                deletions
+                            }}
+                            pageInfo {{
+                                endCursor
+                                hasNextPage
+                            }}
+                        }}
+                    }}
+                }}
+            }}
+            "#,
+            pull_url,
+            after_cursor
+                .as_ref()
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+        );
+
+        let response_body = github_http_post_gql(&query_str)
+            .await
+            .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+        let response: GraphQLResponse = serde_json::from_slice(&response_body)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+        let files = match response
+            .data
+            .and_then(|data| data.resource)
+            .and_then(|resource| resource.files)
+        {
+            Some(files) => files,
+            None => break,
+        };
+
+        for file in files.nodes.unwrap_or_default() {
+            all_files.push(PullFile {
+                path: file.path,
+                additions: file.additions,
+                deletions: file.deletions,
+            });
+        }
+
+        match files.pageInfo {
+            PageInfo {
+                hasNextPage: true,
+                endCursor: Some(cursor),
+            } => after_cursor = Some(cursor),
+            _ => break,
+        }
+    }
+
+    Ok(all_files)
+}
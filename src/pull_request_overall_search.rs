@@ -1,28 +1,47 @@
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{DateTime, Utc};
 
-use anyhow::anyhow;
-use octocrab::{models::issues::Issue, Octocrab};
-use std::env;
-
-use http_req::{
-    request::{Method, Request},
-    uri::Uri,
-};
+use crate::bots::bot_login;
+use crate::contributors::GHOST_LOGIN;
+use crate::issue_links::{connected_issues, merge_pull_links, ConnectionEvent, PullIssueLink};
+use crate::issues_tracker_local::github_http_post_gql;
+use crate::pull_checks::{get_pull_request_checks, PullChecks};
+use crate::pull_files::{get_pull_request_files, PullFile};
+use crate::pull_request_per_repo_search::get_pull_request_issue_refs;
+use crate::pull_reviews::{get_pull_request_reviews, PullReview};
+use crate::reference_parser::extract_pull_links;
+use crate::repository_metadata::escape;
 use serde::{Deserialize, Serialize};
-use std::io::Write;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct OuterPull {
+    pub node_id: String, // GraphQL id, the same pull request found by two windows is written once
     pub title: String,
     pub url: String,
-    pub author: String,
-    pub repository: String, // URL of the repository where the pull request was opened
+    pub author: Option<String>, // None when the account was deleted
+    pub repository: String,     // URL of the repository where the pull request was opened
+    pub repository_avatar: String,
     pub labels: Vec<String>,
     pub reviews: Vec<String>, // authors whose review state is approved
-    pub merged_by: String,
+    pub review_history: Vec<PullReview>,
+    pub connected_issues: Vec<String>,
+    pub issue_links: Vec<PullIssueLink>,
+    pub merged_by: Option<String>, // None if the PR is not merged
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>, // drives the sync watermark
+    pub merged_at: Option<DateTime<Utc>>,
+    pub additions: i32,
+    pub deletions: i32,
+    pub changed_files: i32,
+    pub commit_count: i32,
+    pub files: Vec<PullFile>,
+    pub checks: Option<PullChecks>, // CI state at merge, None for unmerged PRs or repos without CI
 }
 
-pub async fn overall_search_pull_requests(query: &str) -> anyhow::Result<Vec<OuterPull>> {
+// one page of up to 100 results and the cursor of the next one, None after the last page
+pub async fn overall_search_pull_requests_page(
+    query: &str,
+    after_cursor: Option<&str>,
+) -> anyhow::Result<(Vec<OuterPull>, Option<String>)> {
     #[derive(Serialize, Deserialize, Debug)]
     struct GraphQLResponse {
         data: Data,
@@ -53,21 +72,69 @@ pub async fn overall_search_pull_requests(query: &str) -> anyhow::Result<Vec<Out
 
     #[derive(Serialize, Deserialize, Debug)]
     struct PullRequest {
+        id: String,
         title: String,
         url: String,
+        body: Option<String>,
         repository: Repository,
         author: Option<Author>,
         labels: Labels,
-        hasApprovedReview: Reviews,
+        reviews: Reviews,
+        timelineItems: TimelineItems,
+        closingIssuesReferences: ClosingIssues,
         mergedBy: Option<Author>,
+        createdAt: DateTime<Utc>,
+        updatedAt: DateTime<Utc>,
+        mergedAt: Option<DateTime<Utc>>,
+        additions: i32,
+        deletions: i32,
+        changedFiles: i32,
+        commits: Commits,
+        files: Option<Files>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Commits {
+        totalCount: i32,
+        nodes: Vec<CommitNode>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct CommitNode {
+        commit: Commit,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Commit {
+        message: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Files {
+        nodes: Vec<File>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct File {
+        path: String,
+        additions: i32,
+        deletions: i32,
     }
     #[derive(Serialize, Deserialize, Debug)]
     struct Repository {
         url: Option<String>,
+        owner: Option<Owner>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Owner {
+        avatarUrl: Option<String>,
     }
 
     #[derive(Serialize, Deserialize, Debug)]
     struct Author {
+        __typename: Option<String>,
         login: Option<String>,
     }
 
@@ -89,6 +156,7 @@ pub async fn overall_search_pull_requests(query: &str) -> anyhow::Result<Vec<Out
     #[derive(Serialize, Deserialize, Debug)]
     struct Reviews {
         edges: Option<Vec<ReviewEdge>>,
+        pageInfo: PageInfo,
     }
 
     #[derive(Serialize, Deserialize, Debug)]
@@ -98,119 +166,329 @@ pub async fn overall_search_pull_requests(query: &str) -> anyhow::Result<Vec<Out
 
     #[derive(Serialize, Deserialize, Debug)]
     struct Review {
+        url: String,
         author: Option<Author>,
+        state: String,
+        submittedAt: Option<DateTime<Utc>>,
+        authorAssociation: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct TimelineItems {
+        nodes: Vec<TimelineEvent>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct TimelineEvent {
+        __typename: String,
+        subject: Option<Subject>,
     }
-    
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct ClosingIssues {
+        nodes: Vec<Subject>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Subject {
+        url: String,
+    }
+
     let mut all_pulls = Vec::new();
-    let mut after_cursor = None;
-    for _n in 0..10 {
-        let query_str = format!(
-            r#"
-            query {{
-                search(query: "{}", type: ISSUE, first: 100, after: {}) {{
-                    issueCount
-                    edges {{
-                        node {{
-                            ... on PullRequest {{
-                                title
+    let query_str = format!(
+        r#"
+        query {{
+            search(query: "{}", type: ISSUE, first: 100, after: {}) {{
+                issueCount
+                edges {{
+                    node {{
+                        ... on PullRequest {{
+                            id
+                            title
+                            url
+                            body
+                            repository {{
                                 url
-                                repository {{
-                                    url
+                                owner {{
+                                    avatarUrl
                                 }}
-                                author {{
-                                    login
+                            }}
+                            author {{
+                                __typename
+                                login
+                            }}
+                            labels(first: 10) {{
+                                edges {{
+                                    node {{
+                                        name
+                                    }}
                                 }}
-                                labels(first: 10) {{
-                                    edges {{
-                                        node {{
-                                            name
+                            }}
+                            reviews(first: 50) {{
+                                edges {{
+                                    node {{
+                                        url
+                                        author {{
+                                            __typename
+                                            login
                                         }}
+                                        state
+                                        submittedAt
+                                        authorAssociation
                                     }}
                                 }}
-                                hasApprovedReview: reviews(first: 5, states: [APPROVED]) {{
-                                    edges {{
-                                        node {{
-                                            author {{
-                                                login
+                                pageInfo {{
+                                    endCursor
+                                    hasNextPage
+                                }}
+                            }}
+                            timelineItems(first: 10, itemTypes: [CONNECTED_EVENT, DISCONNECTED_EVENT]) {{
+                                nodes {{
+                                    __typename
+                                    ... on ConnectedEvent {{
+                                        subject {{
+                                            ... on Issue {{
+                                                url
+                                            }}
+                                        }}
+                                    }}
+                                    ... on DisconnectedEvent {{
+                                        subject {{
+                                            ... on Issue {{
+                                                url
                                             }}
-                                            state
                                         }}
                                     }}
                                 }}
-                                mergedBy {{
-                                    login
+                                pageInfo {{
+                                    endCursor
+                                    hasNextPage
+                                }}
+                            }}
+                            closingIssuesReferences(first: 10) {{
+                                nodes {{
+                                    url
+                                }}
+                                pageInfo {{
+                                    endCursor
+                                    hasNextPage
+                                }}
+                            }}
+                            mergedBy {{
+                                __typename
+                                login
+                            }}
+                            createdAt
+                            updatedAt
+                            mergedAt
+                            additions
+                            deletions
+                            changedFiles
+                            commits(last: 100) {{
+                                totalCount
+                                nodes {{
+                                    commit {{
+                                        message
+                                    }}
+                                }}
+                            }}
+                            files(first: 100) {{
+                                nodes {{
+                                    path
+                                    additions
+                                    deletions
+                                }}
+                                pageInfo {{
+                                    endCursor
+                                    hasNextPage
                                 }}
                             }}
                         }}
                     }}
-                    pageInfo {{
-                        endCursor
-                        hasNextPage
-                    }}
+                }}
+                pageInfo {{
+                    endCursor
+                    hasNextPage
                 }}
             }}
-            "#,
-            query,
-            after_cursor
-                .as_ref()
-                .map_or(String::from("null"), |c| format!("\"{}\"", c))
-        );
+        }}
+        "#,
+        escape(query),
+        after_cursor
+            .as_ref()
+            .map_or(String::from("null"), |c| format!("\"{}\"", c))
+    );
 
-        let response_body = github_http_post_gql(&query_str).await?;
-        let response: GraphQLResponse = serde_json::from_slice(&response_body)?;
+    let response_body = github_http_post_gql(&query_str).await?;
+    let response: GraphQLResponse = serde_json::from_slice(&response_body)?;
 
-        for edge in response.data.search.edges {
-            let pull = edge.node;
+    for edge in response.data.search.edges {
+        let pull = edge.node;
+        // pull requests are stored under the project of their repository, one without it can't be
+        let Some(repository) = pull.repository.url.clone() else {
+            continue;
+        };
 
-            let labels = pull
-                .labels
-                .edges
-                .as_ref()
-                .unwrap_or(&Vec::new())
-                .into_iter()
-                .filter_map(|edge| edge.node.as_ref())
-                .map(|node| node.name.clone())
-                .collect::<Vec<Option<_>>>();
+        let labels = pull
+            .labels
+            .edges
+            .as_ref()
+            .unwrap_or(&Vec::new())
+            .iter()
+            .filter_map(|edge| edge.node.as_ref())
+            .map(|node| node.name.clone())
+            .collect::<Vec<Option<_>>>();
 
-            let reviews = pull
-                .hasApprovedReview
+        let review_history = if pull.reviews.pageInfo.hasNextPage {
+            get_pull_request_reviews(&pull.url).await?
+        } else {
+            pull.reviews
                 .edges
-                .as_ref()
-                .unwrap_or(&Vec::new())
+                .unwrap_or_default()
                 .into_iter()
-                .filter_map(|edge| edge.node.as_ref())
-                .map(|node| node.author.as_ref().and_then(|author| author.login.clone()))
-                .collect::<Vec<Option<_>>>();
-
-            all_pulls.push(OuterPull {
-                title: pull.title.clone(),
-                url: pull.url.clone(),
-                author: pull
-                    .author
-                    .as_ref()
-                    .and_then(|author| author.login.clone())
-                    .unwrap_or_else(|| String::from("Unknown author")),
-                repository: pull
-                    .repository
-                    .url
-                    .clone()
-                    .unwrap_or_else(|| String::from("Unknown repository")),
-                labels: labels.into_iter().filter_map(|x| x).collect(),
-                reviews: reviews.into_iter().filter_map(|x| x).collect(),
-                merged_by: pull
-                    .mergedBy
-                    .as_ref()
-                    .and_then(|author| author.login.clone())
-                    .unwrap_or_else(|| String::from("Unknown merged_by")),
-            });
+                .filter_map(|edge| edge.node)
+                .map(|review| PullReview {
+                    review_id: review.url,
+                    pull_id: pull.url.clone(),
+                    reviewer: review
+                        .author
+                        .and_then(|author| {
+                            Some(bot_login(author.login?, author.__typename.as_deref()))
+                        })
+                        .unwrap_or_else(|| GHOST_LOGIN.to_string()),
+                    state: review.state,
+                    submitted_at: review.submittedAt,
+                    author_association: review.authorAssociation,
+                })
+                .collect::<Vec<PullReview>>()
+        };
+
+        let reviews = review_history
+            .iter()
+            .filter(|review| review.state == "APPROVED")
+            .map(|review| review.reviewer.clone())
+            .collect::<Vec<String>>();
+
+        let (connected_issues, closing_issues) = if pull.timelineItems.pageInfo.hasNextPage
+            || pull.closingIssuesReferences.pageInfo.hasNextPage
+        {
+            get_pull_request_issue_refs(&pull.url).await?
+        } else {
+            (
+                connected_issues(
+                    &pull
+                        .timelineItems
+                        .nodes
+                        .into_iter()
+                        .filter_map(|event| {
+                            let subject = event.subject?;
+                            Some(ConnectionEvent::from_typename(
+                                &event.__typename,
+                                subject.url,
+                            ))
+                        })
+                        .collect::<Vec<ConnectionEvent>>(),
+                ),
+                pull.closingIssuesReferences
+                    .nodes
+                    .into_iter()
+                    .map(|issue| issue.url)
+                    .collect::<Vec<String>>(),
+            )
+        };
+        let mut issue_links = merge_pull_links(&pull.url, &connected_issues, &closing_issues);
+        // references in the body and commit messages that GitHub didn't link itself
+        let mut texts = vec![pull.body.as_deref().unwrap_or_default()];
+        texts.extend(
+            pull.commits
+                .nodes
+                .iter()
+                .map(|node| node.commit.message.as_str()),
+        );
+        for link in extract_pull_links(&pull.url, &repository, &texts) {
+            if !issue_links
+                .iter()
+                .any(|existing| existing.issue_id == link.issue_id)
+            {
+                issue_links.push(link);
+            }
         }
 
-        match response.data.search.pageInfo {
-            PageInfo {
-                hasNextPage: true,
-                endCursor: Some(cursor),
-            } => after_cursor = Some(cursor),
-            _ => break,
+        let files = match pull.files {
+            Some(files) if files.pageInfo.hasNextPage => get_pull_request_files(&pull.url).await?,
+            Some(files) => files
+                .nodes
+                .into_iter()
+                .map(|file| PullFile {
+                    path: file.path,
+                    additions: file.additions,
+                    deletions: file.deletions,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let checks = if pull.mergedAt.is_some() {
+            get_pull_request_checks(&pull.url).await?
+        } else {
+            None
+        };
+
+        all_pulls.push(OuterPull {
+            node_id: pull.id.clone(),
+            title: pull.title.clone(),
+            url: pull.url.clone(),
+            author: pull
+                .author
+                .and_then(|author| Some(bot_login(author.login?, author.__typename.as_deref()))),
+            repository,
+            repository_avatar: pull
+                .repository
+                .owner
+                .and_then(|owner| owner.avatarUrl)
+                .unwrap_or_default(),
+            labels: labels.into_iter().flatten().collect(),
+            reviews,
+            review_history,
+            connected_issues,
+            issue_links,
+            merged_by: pull
+                .mergedBy
+                .and_then(|author| Some(bot_login(author.login?, author.__typename.as_deref()))),
+            created_at: pull.createdAt,
+            updated_at: pull.updatedAt,
+            merged_at: pull.mergedAt,
+            additions: pull.additions,
+            deletions: pull.deletions,
+            changed_files: pull.changedFiles,
+            commit_count: pull.commits.totalCount,
+            files,
+            checks,
+        });
+    }
+
+    let next_cursor = match response.data.search.pageInfo {
+        PageInfo {
+            hasNextPage: true,
+            endCursor: Some(cursor),
+        } => Some(cursor),
+        _ => None,
+    };
+    Ok((all_pulls, next_cursor))
+}
+
+pub async fn overall_search_pull_requests(query: &str) -> anyhow::Result<Vec<OuterPull>> {
+    let mut all_pulls = Vec::new();
+    let mut after_cursor: Option<String> = None;
+    for _ in 0..10 {
+        let (page, next_cursor) =
+            overall_search_pull_requests_page(query, after_cursor.as_deref()).await?;
+        all_pulls.extend(page);
+        match next_cursor {
+            Some(cursor) => after_cursor = Some(cursor),
+            None => break,
         }
     }
     Ok(all_pulls)
@@ -1,95 +1,52 @@
-use chrono::{Duration, NaiveDate, Utc};
-
 use anyhow::anyhow;
-use octocrab::{models::issues::Issue, Octocrab};
-use std::env;
 
-use http_req::{
-    request::{Method, Request},
-    uri::Uri,
-};
+use crate::issue_links::{connected_issues, ConnectionEvent};
+use crate::issues_tracker_local::{github_http_post_gql, graphql_error, GraphQLError};
 use serde::{Deserialize, Serialize};
-use std::io::Write;
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct SimplePull {
-    pub title: String,
-    pub url: String,
-    pub author: String,
-    pub connected_issues: Vec<String>,
-    pub labels: Vec<String>,
-    pub reviews: Vec<String>,      // authors whose review state is approved
-    pub merged_by: Option<String>, // This field can be empty if the PR is not merged
-}
 
-pub async fn get_per_repo_pull_requests(query: &str) -> anyhow::Result<Vec<SimplePull>> {
+// walks every page of CONNECTED_EVENT / DISCONNECTED_EVENT items and closingIssuesReferences of
+// one pull_request, returns (connected_issues, closing_issues)
+pub async fn get_pull_request_issue_refs(
+    pull_url: &str,
+) -> anyhow::Result<(Vec<String>, Vec<String>)> {
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct GraphQLResponse {
-        data: Data,
+        data: Option<Data>,
+        errors: Option<Vec<GraphQLError>>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct Data {
-        search: Search,
-    }
-
-    #[derive(Serialize, Deserialize, Clone, Debug)]
-    struct Search {
-        issueCount: i32,
-        nodes: Vec<Node>,
-        pageInfo: PageInfo,
-    }
-
-    #[derive(Serialize, Deserialize, Clone, Debug)]
-    struct Node {
-        title: String,
-        url: String,
-        author: Author,
-        timelineItems: TimelineItems,
-        labels: Labels,
-        reviews: Reviews,
-        mergedBy: Option<Author>,
+        resource: Option<Resource>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
-    struct Author {
-        login: String,
+    struct Resource {
+        timelineItems: Option<TimelineItems>,
+        closingIssuesReferences: Option<ClosingIssues>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct TimelineItems {
         nodes: Vec<TimelineEvent>,
+        pageInfo: PageInfo,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct TimelineEvent {
+        __typename: String,
         subject: Option<Subject>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
-    struct Subject {
-        url: String,
-    }
-
-    #[derive(Serialize, Deserialize, Clone, Debug)]
-    struct Labels {
-        nodes: Vec<Label>,
-    }
-
-    #[derive(Serialize, Deserialize, Clone, Debug)]
-    struct Label {
-        name: String,
-    }
-
-    #[derive(Serialize, Deserialize, Clone, Debug)]
-    struct Reviews {
-        nodes: Vec<Review>,
+    struct ClosingIssues {
+        nodes: Vec<Subject>,
+        pageInfo: PageInfo,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
-    struct Review {
-        author: Author,
-        state: String,
+    struct Subject {
+        url: String,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -98,108 +55,116 @@ pub async fn get_per_repo_pull_requests(query: &str) -> anyhow::Result<Vec<Simpl
         hasNextPage: bool,
     }
 
-    let mut simplified_pulls = Vec::new();
-    let mut after_cursor: Option<String> = None;
+    let mut connection_events = Vec::new();
+    let mut closing_issues = Vec::new();
+    let mut timeline_cursor: Option<String> = None;
+    let mut closing_cursor: Option<String> = None;
+    let mut timeline_done = false;
+    let mut closing_done = false;
 
-    for _n in 0..10 {
+    // both connections are paged in the same request until each runs out
+    while !(timeline_done && closing_done) {
         let query_str = format!(
             r#"
             query {{
-                search(query: "{}", type: ISSUE, first: 100, after: {}) {{
-                    issueCount
-                    nodes {{
-                        ... on PullRequest {{
-                            title
-                            url
-                            author {{
-                                login
-                            }}
-                            timelineItems(first: 5, itemTypes: [CONNECTED_EVENT]) {{
-                                nodes {{
-                                    ... on ConnectedEvent {{
-                                        subject {{
-                                            ... on Issue {{
-                                                url
-                                            }}
+                resource(url: "{}") {{
+                    ... on PullRequest {{
+                        timelineItems(first: {}, after: {}, itemTypes: [CONNECTED_EVENT, DISCONNECTED_EVENT]) {{
+                            nodes {{
+                                __typename
+                                ... on ConnectedEvent {{
+                                    subject {{
+                                        ... on Issue {{
+                                            url
                                         }}
                                     }}
                                 }}
-                            }}
-                            labels(first: 10) {{
-                                nodes {{
-                                    name
-                                }}
-                            }}
-                            reviews(first: 5, states: [APPROVED]) {{
-                                nodes {{
-                                    author {{
-                                        login
+                                ... on DisconnectedEvent {{
+                                    subject {{
+                                        ... on Issue {{
+                                            url
+                                        }}
                                     }}
-                                    state
                                 }}
                             }}
-                            mergedBy {{
-                                login
+                            pageInfo {{
+                                endCursor
+                                hasNextPage
+                            }}
+                        }}
+                        closingIssuesReferences(first: {}, after: {}) {{
+                            nodes {{
+                                url
+                            }}
+                            pageInfo {{
+                                endCursor
+                                hasNextPage
                             }}
                         }}
-                    }}
-                    pageInfo {{
-                        endCursor
-                        hasNextPage
                     }}
                 }}
             }}
             "#,
-            query,
-            after_cursor
+            pull_url,
+            if timeline_done { 0 } else { 100 },
+            timeline_cursor
+                .as_ref()
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+            if closing_done { 0 } else { 100 },
+            closing_cursor
                 .as_ref()
-                .map_or(String::from("null"), |c| format!("\"{}\"", c))
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
         );
 
         let response_body = github_http_post_gql(&query_str).await?;
         let response: GraphQLResponse = serde_json::from_slice(&response_body)?;
 
-        for node in response.data.search.nodes {
-            let connected_issues = node
-                .timelineItems
-                .nodes
-                .iter()
-                .filter_map(|event| event.subject.as_ref().map(|subject| subject.url.clone()))
-                .collect::<Vec<String>>();
-
-            let labels = node
-                .labels
-                .nodes
-                .iter()
-                .map(|label| label.name.clone())
-                .collect::<Vec<String>>();
-
-            let reviews = node
-                .reviews
-                .nodes
-                .iter()
-                .filter(|review| review.state == "APPROVED")
-                .map(|review| review.author.login.clone())
-                .collect::<Vec<String>>();
-
-            simplified_pulls.push(SimplePull {
-                title: node.title,
-                url: node.url,
-                author: node.author.login,
-                connected_issues,
-                labels,
-                reviews,
-                merged_by: node.mergedBy.as_ref().map(|author| author.login.clone()),
-            });
+        if response.errors.is_some() {
+            return Err(graphql_error(&response.errors));
         }
-        match response.data.search.pageInfo {
-            PageInfo {
-                hasNextPage: true,
-                endCursor: Some(cursor),
-            } => after_cursor = Some(cursor),
-            _ => break,
+        let resource = response
+            .data
+            .and_then(|data| data.resource)
+            .ok_or_else(|| anyhow!("no pull request found for {}", pull_url))?;
+
+        if !timeline_done {
+            match resource.timelineItems {
+                Some(items) => {
+                    connection_events.extend(items.nodes.into_iter().filter_map(|event| {
+                        let subject = event.subject?;
+                        Some(ConnectionEvent::from_typename(
+                            &event.__typename,
+                            subject.url,
+                        ))
+                    }));
+                    match items.pageInfo {
+                        PageInfo {
+                            hasNextPage: true,
+                            endCursor: Some(cursor),
+                        } => timeline_cursor = Some(cursor),
+                        _ => timeline_done = true,
+                    }
+                }
+                None => timeline_done = true,
+            }
+        }
+
+        if !closing_done {
+            match resource.closingIssuesReferences {
+                Some(issues) => {
+                    closing_issues.extend(issues.nodes.into_iter().map(|issue| issue.url));
+                    match issues.pageInfo {
+                        PageInfo {
+                            hasNextPage: true,
+                            endCursor: Some(cursor),
+                        } => closing_cursor = Some(cursor),
+                        _ => closing_done = true,
+                    }
+                }
+                None => closing_done = true,
+            }
         }
     }
 
-    Ok(simplified_pulls)
+    Ok((connected_issues(&connection_events), closing_issues))
 }
@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+
+use crate::bots::{bot_login, BotFilter};
+use crate::contributors::GHOST_LOGIN;
+use crate::issues_tracker_local::{github_http_post_gql, graphql_error, GraphQLError};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+// authorAssociation values whose approval counts towards eligibility
+pub const MAINTAINER_ASSOCIATIONS: [&str; 2] = ["MEMBER", "OWNER"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PullReview {
+    pub review_id: String, // url of the review
+    pub pull_id: String,   // url of the pull_request
+    pub reviewer: String,
+    pub state: String, // APPROVED, CHANGES_REQUESTED, COMMENTED, DISMISSED or PENDING
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub author_association: String, // MEMBER, OWNER, COLLABORATOR, CONTRIBUTOR, ...
+}
+
+// every review of a pull_request in all states, oldest first
+pub async fn get_pull_request_reviews(pull_url: &str) -> anyhow::Result<Vec<PullReview>> {
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct GraphQLResponse {
+        data: Option<Data>,
+        errors: Option<Vec<GraphQLError>>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Data {
+        resource: Option<Resource>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Resource {
+        reviews: Option<Reviews>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Reviews {
+        nodes: Option<Vec<Review>>,
+        pageInfo: PageInfo,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Review {
+        url: String,
+        author: Option<Author>,
+        state: String,
+        submittedAt: Option<DateTime<Utc>>,
+        authorAssociation: String,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Author {
+        __typename: Option<String>,
+        login: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct PageInfo {
+        endCursor: Option<String>,
+        hasNextPage: bool,
+    }
+
+    let mut all_reviews = Vec::new();
+    let mut after_cursor: Option<String> = None;
+
+    loop {
+        let query_str = format!(
+            r#"
+            query {{
+                resource(url: "{}") {{
+                    ... on PullRequest {{
+                        reviews(first: 100, after: {}) {{
+                            nodes {{
+                                url
+                                author {{
+                                    __typename
+                                    login
+                                }}
+                                state
+                                submittedAt
+                                authorAssociation
+                            }}
+                            pageInfo {{
+                                endCursor
+                                hasNextPage
+                            }}
+                        }}
+                    }}
+                }}
+            }}
+            "#,
+            pull_url,
+            after_cursor
+                .as_ref()
+                .map_or(String::from("null"), |c| format!("\"{}\"", c)),
+        );
+
+        let response_body = github_http_post_gql(&query_str)
+            .await
+            .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+        let response: GraphQLResponse = serde_json::from_slice(&response_body)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+        if response.errors.is_some() {
+            return Err(graphql_error(&response.errors));
+        }
+        let reviews = response
+            .data
+            .and_then(|data| data.resource)
+            .and_then(|resource| resource.reviews)
+            .ok_or_else(|| anyhow!("no reviews found for {}", pull_url))?;
+
+        for review in reviews.nodes.unwrap_or_default() {
+            all_reviews.push(PullReview {
+                review_id: review.url,
+                pull_id: pull_url.to_string(),
+                reviewer: review
+                    .author
+                    .and_then(|author| Some(bot_login(author.login?, author.__typename.as_deref())))
+                    .unwrap_or_else(|| GHOST_LOGIN.to_string()),
+                state: review.state,
+                submitted_at: review.submittedAt,
+                author_association: review.authorAssociation,
+            });
+        }
+
+        match reviews.pageInfo {
+            PageInfo {
+                hasNextPage: true,
+                endCursor: Some(cursor),
+            } => after_cursor = Some(cursor),
+            _ => break,
+        }
+    }
+
+    Ok(all_reviews)
+}
+
+// a reviewer's standing is their latest APPROVED, CHANGES_REQUESTED or DISMISSED review,
+// plain comments don't change it; approvals count only from members or owners, never from bots
+pub fn eligible_approvers(reviews: &[PullReview], bots: &BotFilter) -> Vec<String> {
+    let mut latest: Vec<&PullReview> = Vec::new();
+
+    for review in reviews {
+        if !matches!(
+            review.state.as_str(),
+            "APPROVED" | "CHANGES_REQUESTED" | "DISMISSED"
+        ) {
+            continue;
+        }
+        match latest.iter_mut().find(|r| r.reviewer == review.reviewer) {
+            Some(current) => {
+                if review.submitted_at >= current.submitted_at {
+                    *current = review;
+                }
+            }
+            None => latest.push(review),
+        }
+    }
+
+    latest
+        .into_iter()
+        .filter(|review| review.state == "APPROVED")
+        .filter(|review| MAINTAINER_ASSOCIATIONS.contains(&review.author_association.as_str()))
+        .filter(|review| !bots.is_bot(&review.reviewer))
+        .map(|review| review.reviewer.clone())
+        .collect()
+}
+
+pub fn has_eligible_approval(reviews: &[PullReview], bots: &BotFilter) -> bool {
+    !eligible_approvers(reviews, bots).is_empty()
+}
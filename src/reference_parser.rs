@@ -0,0 +1,322 @@
+use crate::issue_links::{LinkOrigin, PullIssueLink};
+use serde::{Deserialize, Serialize};
+
+// every keyword GitHub accepts for closing an issue, matched case-insensitively
+pub const CLOSING_KEYWORDS: [&str; 9] = [
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TextReference {
+    pub issue_id: String, // url of the referenced issue, always in the /issues/ form
+    pub keyword: Option<String>, // the closing keyword in front of the reference, if any
+}
+
+impl TextReference {
+    pub fn is_closing(&self) -> bool {
+        self.keyword.is_some()
+    }
+}
+
+// "https://github.com/owner/repo" -> ("owner", "repo")
+fn split_repository(repository: &str) -> Option<(String, String)> {
+    let path = repository
+        .trim_end_matches('/')
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.")
+        .strip_prefix("github.com/")?;
+
+    let mut parts = path.split('/');
+    let owner = parts.next().filter(|s| is_name(s))?;
+    let repo = parts.next().filter(|s| is_name(s))?;
+    Some((owner.to_string(), repo.to_string()))
+}
+
+fn is_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse::<u64>().ok().filter(|n| *n > 0)
+}
+
+fn issue_url(owner: &str, repo: &str, number: u64) -> String {
+    format!("https://github.com/{owner}/{repo}/issues/{number}")
+}
+
+// accepts "#12", "GH-12", "owner/repo#12" and issue or pull_request urls with or without scheme
+fn parse_reference(token: &str, base: Option<&(String, String)>) -> Option<String> {
+    if let Some(number) = token.strip_prefix('#') {
+        let (owner, repo) = base?;
+        return parse_number(number).map(|n| issue_url(owner, repo, n));
+    }
+
+    if let Some(number) = token
+        .get(..3)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("gh-"))
+        .and_then(|_| token.get(3..))
+    {
+        let (owner, repo) = base?;
+        return parse_number(number).map(|n| issue_url(owner, repo, n));
+    }
+
+    let path = token
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.");
+
+    if let Some(path) = path.strip_prefix("github.com/") {
+        let mut parts = path.split('/');
+        let owner = parts.next().filter(|s| is_name(s))?;
+        let repo = parts.next().filter(|s| is_name(s))?;
+        match parts.next()? {
+            "issues" | "pull" => {}
+            _ => return None,
+        }
+        // drop anchors such as #issuecomment-123 or trailing /files
+        let number = parts.next()?.split(['#', '?']).next()?;
+        return parse_number(number).map(|n| issue_url(owner, repo, n));
+    }
+
+    let (repo_path, number) = token.split_once('#')?;
+    let (owner, repo) = repo_path.split_once('/')?;
+    if is_name(owner) && is_name(repo) {
+        return parse_number(number).map(|n| issue_url(owner, repo, n));
+    }
+
+    None
+}
+
+fn trim_token(token: &str) -> &str {
+    token
+        .trim_start_matches(['(', '[', '<', '*', '_', '`', '"', '\''])
+        .trim_end_matches([
+            '.', ',', ';', ':', '!', '?', ')', ']', '>', '*', '_', '`', '"', '\'',
+        ])
+}
+
+// scans free text (bodies, commit messages, comments) for issue references, relative ones are
+// resolved against `repository`, the url of the repo the text belongs to
+pub fn extract_references(text: &str, repository: &str) -> Vec<TextReference> {
+    let base = split_repository(repository);
+    let mut references: Vec<TextReference> = Vec::new();
+    let mut in_code_block = false;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let mut pending_keyword: Option<String> = None;
+        for raw in line.split_whitespace() {
+            let token = trim_token(raw);
+            let lowered = token.to_lowercase();
+
+            if CLOSING_KEYWORDS.contains(&lowered.as_str()) {
+                pending_keyword = Some(lowered);
+                continue;
+            }
+
+            // "Fixes:#12" keeps the keyword glued to the reference
+            let (keyword, token) = match lowered.split_once(':') {
+                Some((head, _)) if CLOSING_KEYWORDS.contains(&head) => (
+                    Some(head.to_string()),
+                    token.get(head.len() + 1..).unwrap_or_default(),
+                ),
+                _ => (pending_keyword.take(), token),
+            };
+
+            if let Some(issue_id) = parse_reference(token, base.as_ref()) {
+                match references.iter_mut().find(|r| r.issue_id == issue_id) {
+                    Some(existing) => {
+                        if existing.keyword.is_none() {
+                            existing.keyword = keyword;
+                        }
+                    }
+                    None => references.push(TextReference { issue_id, keyword }),
+                }
+            }
+        }
+    }
+
+    references
+}
+
+// links for the reconciler, closing references count as keyword links and the rest as mentions;
+// the pull_request's own number is never linked to itself
+pub fn extract_pull_links(pull_id: &str, repository: &str, texts: &[&str]) -> Vec<PullIssueLink> {
+    let own_issue_id = pull_id.replace("/pull/", "/issues/");
+    let mut links: Vec<PullIssueLink> = Vec::new();
+
+    for text in texts {
+        for reference in extract_references(text, repository) {
+            if reference.issue_id == own_issue_id {
+                continue;
+            }
+            let origin = if reference.is_closing() {
+                LinkOrigin::Keyword
+            } else {
+                LinkOrigin::Mention
+            };
+
+            match links.iter_mut().find(|l| l.issue_id == reference.issue_id) {
+                Some(existing) => {
+                    if origin == LinkOrigin::Keyword {
+                        existing.origin = origin;
+                    }
+                }
+                None => links.push(PullIssueLink {
+                    pull_id: pull_id.to_string(),
+                    issue_id: reference.issue_id,
+                    origin,
+                }),
+            }
+        }
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPO: &str = "https://github.com/owner/repo";
+
+    fn reference(issue_id: &str, keyword: Option<&str>) -> TextReference {
+        TextReference {
+            issue_id: issue_id.to_string(),
+            keyword: keyword.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn resolves_relative_references_against_the_repository() {
+        assert_eq!(
+            extract_references("see #12 and GH-13", REPO),
+            vec![
+                reference("https://github.com/owner/repo/issues/12", None),
+                reference("https://github.com/owner/repo/issues/13", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_references_need_a_repository() {
+        assert!(extract_references("see #12", "").is_empty());
+    }
+
+    #[test]
+    fn parses_references_to_other_repositories() {
+        assert_eq!(
+            extract_references("part of other/project#5.", REPO),
+            vec![reference("https://github.com/other/project/issues/5", None)]
+        );
+    }
+
+    #[test]
+    fn parses_issue_and_pull_request_urls() {
+        assert_eq!(
+            extract_references(
+                "(https://github.com/a/b/issues/1) github.com/c/d/pull/2#issuecomment-3",
+                REPO
+            ),
+            vec![
+                reference("https://github.com/a/b/issues/1", None),
+                reference("https://github.com/c/d/issues/2", None),
+            ]
+        );
+        assert!(extract_references("https://github.com/a/b/commit/1", REPO).is_empty());
+    }
+
+    #[test]
+    fn keeps_the_closing_keyword_right_before_a_reference() {
+        assert_eq!(
+            extract_references("Fixes #1, RESOLVES: #2, closes:#3", REPO),
+            vec![
+                reference("https://github.com/owner/repo/issues/1", Some("fixes")),
+                reference("https://github.com/owner/repo/issues/2", Some("resolves")),
+                reference("https://github.com/owner/repo/issues/3", Some("closes")),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_keyword_followed_by_other_words_is_not_closing() {
+        assert_eq!(
+            extract_references("fixes the crash in #4", REPO),
+            vec![reference("https://github.com/owner/repo/issues/4", None)]
+        );
+    }
+
+    #[test]
+    fn a_keyword_does_not_carry_over_to_the_next_line() {
+        assert_eq!(
+            extract_references("fixes\n#4", REPO),
+            vec![reference("https://github.com/owner/repo/issues/4", None)]
+        );
+    }
+
+    #[test]
+    fn a_later_closing_reference_upgrades_an_earlier_mention() {
+        assert_eq!(
+            extract_references("see #4, then fixes #4", REPO),
+            vec![reference(
+                "https://github.com/owner/repo/issues/4",
+                Some("fixes")
+            )]
+        );
+    }
+
+    #[test]
+    fn skips_fenced_code_blocks() {
+        let text = "```\nfixes #1\n```\ncloses #2";
+        assert_eq!(
+            extract_references(text, REPO),
+            vec![reference(
+                "https://github.com/owner/repo/issues/2",
+                Some("closes")
+            )]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_numbers() {
+        assert!(extract_references("#0 #12abc # owner/repo#", REPO).is_empty());
+    }
+
+    #[test]
+    fn pull_links_skip_the_pull_request_itself_and_merge_texts() {
+        let links = extract_pull_links(
+            "https://github.com/owner/repo/pull/9",
+            REPO,
+            &["Fixes #3, see #9", "mentions #4", "closes #4"],
+        );
+        assert_eq!(
+            links
+                .iter()
+                .map(|link| (link.issue_id.as_str(), link.origin))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "https://github.com/owner/repo/issues/3",
+                    LinkOrigin::Keyword
+                ),
+                (
+                    "https://github.com/owner/repo/issues/4",
+                    LinkOrigin::Keyword
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use crate::issues_tracker_local::{github_http_post_gql, graphql_error, GraphQLError};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DEFAULT_OPT_IN_TOPIC: &str = "hacktoberfest";
+
+// aliases packed into one query, keeps each request well under the GraphQL node limit
+pub const DEFAULT_BATCH_SIZE: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RepoMetadata {
+    pub project_id: String, // url of the repo
+    pub project_logo: String,
+    pub description: Option<String>,
+    pub primary_language: Option<String>,
+    pub languages: Vec<String>, // largest first
+    pub topics: Vec<String>,
+    pub license: Option<String>, // spdx id
+    pub stars: i64,
+    pub is_archived: bool,
+    pub is_fork: bool,
+    pub default_branch: Option<String>, // None for empty repos
+}
+
+impl RepoMetadata {
+    // archived repos can't accept contributions, so they never count as opted in
+    pub fn is_opted_in(&self, opt_in_topic: &str) -> bool {
+        !self.is_archived
+            && self
+                .topics
+                .iter()
+                .any(|topic| topic.eq_ignore_ascii_case(opt_in_topic))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchLookup<T> {
+    pub found: Vec<(String, T)>, // keyed by the input, "owner/repo" or the url
+    pub not_found: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Repository {
+    url: String,
+    description: Option<String>,
+    owner: Owner,
+    primaryLanguage: Option<Named>,
+    languages: Option<Languages>,
+    repositoryTopics: RepositoryTopics,
+    licenseInfo: Option<License>,
+    stargazerCount: i64,
+    isArchived: bool,
+    isFork: bool,
+    defaultBranchRef: Option<Named>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Owner {
+    avatarUrl: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Languages {
+    nodes: Vec<Named>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RepositoryTopics {
+    nodes: Vec<TopicNode>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TopicNode {
+    topic: Named,
+}
+
+#[derive(Serialize, Deserialize)]
+struct License {
+    spdxId: Option<String>,
+}
+
+impl From<Repository> for RepoMetadata {
+    fn from(repository: Repository) -> Self {
+        RepoMetadata {
+            project_id: repository.url,
+            project_logo: repository.owner.avatarUrl,
+            description: repository.description,
+            primary_language: repository.primaryLanguage.map(|language| language.name),
+            languages: repository
+                .languages
+                .map(|languages| languages.nodes.into_iter().map(|l| l.name).collect())
+                .unwrap_or_default(),
+            topics: repository
+                .repositoryTopics
+                .nodes
+                .into_iter()
+                .map(|node| node.topic.name)
+                .collect(),
+            license: repository.licenseInfo.and_then(|license| license.spdxId),
+            stars: repository.stargazerCount,
+            is_archived: repository.isArchived,
+            is_fork: repository.isFork,
+            default_branch: repository.defaultBranchRef.map(|branch| branch.name),
+        }
+    }
+}
+
+const REPOSITORY_FIELDS: &str = r#"
+    fragment repositoryFields on Repository {
+        url
+        description
+        owner {
+            avatarUrl
+        }
+        primaryLanguage {
+            name
+        }
+        languages(first: 20, orderBy: {field: SIZE, direction: DESC}) {
+            nodes {
+                name
+            }
+        }
+        repositoryTopics(first: 20) {
+            nodes {
+                topic {
+                    name
+                }
+            }
+        }
+        licenseInfo {
+            spdxId
+        }
+        stargazerCount
+        isArchived
+        isFork
+        defaultBranchRef {
+            name
+        }
+    }
+"#;
+
+#[derive(Deserialize)]
+struct AliasedResponse {
+    data: Option<HashMap<String, serde_json::Value>>,
+    errors: Option<Vec<GraphQLError>>,
+}
+
+impl AliasedResponse {
+    // missing entries come back as null next to a NOT_FOUND error and are left for the caller to
+    // report; any other error (rate limits, timeouts, permissions) fails the whole query, even
+    // with partial data, so an entry is never reported missing because its lookup failed
+    fn into_data(self) -> anyhow::Result<HashMap<String, serde_json::Value>> {
+        let failures = self
+            .errors
+            .iter()
+            .flatten()
+            .filter(|error| !error.is_not_found())
+            .cloned()
+            .collect::<Vec<GraphQLError>>();
+        if !failures.is_empty() {
+            return Err(graphql_error(&Some(failures)));
+        }
+
+        match self.data {
+            Some(data) => Ok(data),
+            None if self.errors.is_some() => Err(graphql_error(&self.errors)),
+            None => Err(anyhow!("Failed to deserialize response: no data")),
+        }
+    }
+}
+
+// posts one aliased query, see `AliasedResponse::into_data` for the errors it tolerates
+pub async fn post_aliased_query(
+    selections: &[String],
+    fragments: &str,
+) -> anyhow::Result<HashMap<String, serde_json::Value>> {
+    let query_str = format!("query {{\n{}\n}}\n{}", selections.join("\n"), fragments);
+
+    let response_body = github_http_post_gql(&query_str)
+        .await
+        .map_err(|e| anyhow!("Failed to post GraphQL query: {}", e))?;
+
+    let response: AliasedResponse = serde_json::from_slice(&response_body)
+        .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+    response.into_data()
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// looks up many repos with batch_size of them per request, results keep the order of `repos`
+pub async fn get_repositories_metadata(
+    repos: &[(&str, &str)],
+    batch_size: usize,
+) -> anyhow::Result<BatchLookup<RepoMetadata>> {
+    let mut lookup = BatchLookup {
+        found: Vec::new(),
+        not_found: Vec::new(),
+    };
+
+    for batch in repos.chunks(batch_size.max(1)) {
+        let selections = batch
+            .iter()
+            .enumerate()
+            .map(|(i, (owner, repo))| {
+                format!(
+                    r#"r{}: repository(owner: "{}", name: "{}") {{ ...repositoryFields }}"#,
+                    i,
+                    escape(owner),
+                    escape(repo)
+                )
+            })
+            .collect::<Vec<String>>();
+
+        let mut data = post_aliased_query(&selections, REPOSITORY_FIELDS).await?;
+
+        for (i, (owner, repo)) in batch.iter().enumerate() {
+            let key = format!("{owner}/{repo}");
+            match data.remove(&format!("r{i}")) {
+                Some(value) if !value.is_null() => {
+                    let repository: Repository = serde_json::from_value(value)
+                        .map_err(|e| anyhow!("Failed to deserialize {}: {}", key, e))?;
+                    lookup.found.push((key, repository.into()));
+                }
+                _ => lookup.not_found.push(key),
+            }
+        }
+    }
+
+    Ok(lookup)
+}
+
+// resource(url:) for many urls at once, `selection` is the body applied to every resource,
+// e.g. "... on Issue { title state }"; results are left as json for the caller to shape
+pub async fn get_resources(
+    urls: &[String],
+    selection: &str,
+    batch_size: usize,
+) -> anyhow::Result<BatchLookup<serde_json::Value>> {
+    let mut lookup = BatchLookup {
+        found: Vec::new(),
+        not_found: Vec::new(),
+    };
+
+    for batch in urls.chunks(batch_size.max(1)) {
+        let selections = batch
+            .iter()
+            .enumerate()
+            .map(|(i, url)| {
+                format!(
+                    r#"r{}: resource(url: "{}") {{ __typename {} }}"#,
+                    i,
+                    escape(url),
+                    selection
+                )
+            })
+            .collect::<Vec<String>>();
+
+        let mut data = post_aliased_query(&selections, "").await?;
+
+        for (i, url) in batch.iter().enumerate() {
+            match data.remove(&format!("r{i}")) {
+                Some(value) if !value.is_null() => lookup.found.push((url.clone(), value)),
+                _ => lookup.not_found.push(url.clone()),
+            }
+        }
+    }
+
+    Ok(lookup)
+}
+
+pub async fn get_repository_metadata(owner: &str, repo: &str) -> anyhow::Result<RepoMetadata> {
+    get_repositories_metadata(&[(owner, repo)], 1)
+        .await?
+        .found
+        .pop()
+        .map(|(_, metadata)| metadata)
+        .ok_or_else(|| anyhow!("Repository {}/{} not found", owner, repo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response(body: serde_json::Value) -> AliasedResponse {
+        serde_json::from_value(body).expect("valid response")
+    }
+
+    #[test]
+    fn not_found_entries_are_left_null() {
+        let data = response(json!({
+            "data": {"r0": {"url": "https://github.com/o/r"}, "r1": null},
+            "errors": [{"type": "NOT_FOUND", "path": ["r1"], "message": "Could not resolve"}]
+        }))
+        .into_data()
+        .expect("not found is not a failure");
+        assert!(data["r1"].is_null());
+        assert!(!data["r0"].is_null());
+    }
+
+    #[test]
+    fn other_errors_fail_despite_partial_data() {
+        let rate_limited = response(json!({
+            "data": {"r0": {"url": "https://github.com/o/r"}, "r1": null},
+            "errors": [
+                {"type": "NOT_FOUND", "path": ["r0"], "message": "Could not resolve"},
+                {"type": "RATE_LIMITED", "message": "API rate limit exceeded"}
+            ]
+        }));
+        let error = rate_limited.into_data().expect_err("rate limit fails");
+        assert!(error.to_string().contains("rate limit"));
+
+        // a timeout has no type at all
+        let timed_out = response(json!({
+            "data": {"r0": null},
+            "errors": [{"path": ["r0"], "message": "Something went wrong"}]
+        }));
+        assert!(timed_out.into_data().is_err());
+    }
+
+    #[test]
+    fn no_data_fails() {
+        let not_found = response(json!({
+            "data": null,
+            "errors": [{"type": "NOT_FOUND", "message": "Could not resolve"}]
+        }));
+        assert!(not_found.into_data().is_err());
+        assert!(response(json!({})).into_data().is_err());
+    }
+}
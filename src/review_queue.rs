@@ -0,0 +1,250 @@
+use crate::db_ops::{approve_project_per_issue, decline_issue};
+use crate::db_updater_local::{get_issue, get_spam_score, list_comments, list_queue_issue_ids};
+use crate::listing::{CommentRow, IssueRow};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{
+    self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+use sqlx::postgres::PgPool;
+use std::io::{stdout, Write};
+
+const KEYS_HELP: &str = "a approve  d decline  s skip  q quit";
+
+// everything a reviewer looks at before deciding on one issue
+#[derive(Clone, Debug)]
+pub struct QueueItem {
+    pub issue: IssueRow,
+    pub comments: Vec<CommentRow>,
+    pub spam_score: Option<i32>, // of the linked pull_request
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReviewSummary {
+    pub approved: usize,
+    pub declined: usize,
+    pub skipped: usize,
+}
+
+pub async fn load_queue_item(pool: &PgPool, issue_id: &str) -> anyhow::Result<Option<QueueItem>> {
+    let Some(issue) = get_issue(pool, issue_id).await? else {
+        return Ok(None);
+    };
+    let comments = list_comments(pool, issue_id).await?;
+    let spam_score = match &issue.linked_pr {
+        Some(pull_id) => get_spam_score(pool, pull_id).await?,
+        None => None,
+    };
+
+    Ok(Some(QueueItem {
+        issue,
+        comments,
+        spam_score,
+    }))
+}
+
+// raw mode and the alternate screen for as long as the queue runs, restored however it ends
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> anyhow::Result<Self> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+// hard wraps on characters, the description is free text and rarely worth a smarter layout
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    text.lines()
+        .flat_map(|line| {
+            let chars = line.chars().collect::<Vec<char>>();
+            if chars.is_empty() {
+                vec![String::new()]
+            } else {
+                chars
+                    .chunks(width)
+                    .map(|chunk| chunk.iter().collect::<String>())
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+fn item_lines(item: &QueueItem, position: usize, total: usize, width: usize) -> Vec<String> {
+    let issue = &item.issue;
+    let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| String::from("-"));
+
+    let mut lines = vec![
+        format!("[{position}/{total}] {}", issue.issue_id),
+        format!("title:     {}", issue.title),
+        format!(
+            "assignee:  {}   budget: {}",
+            or_none(&issue.assignee),
+            issue
+                .budget
+                .map_or_else(|| String::from("-"), |budget| budget.to_string())
+        ),
+        format!(
+            "linked pr: {}   spam score: {}",
+            or_none(&issue.linked_pr),
+            item.spam_score
+                .map_or_else(|| String::from("-"), |score| score.to_string())
+        ),
+        format!(
+            "effort:    {} PRs, +{}/-{} in {} files, {} commits",
+            issue.pulls, issue.additions, issue.deletions, issue.changed_files, issue.commits
+        ),
+        String::new(),
+    ];
+    lines.extend(wrap(&issue.description, width));
+    lines.push(String::new());
+    lines.push(format!("comments ({}):", item.comments.len()));
+    for comment in &item.comments {
+        let first_line = comment.content.lines().next().unwrap_or_default();
+        lines.push(format!("  {}: {}", comment.creator, first_line));
+    }
+    lines
+}
+
+// the item fills the screen above two lines for the prompt and the last outcome
+fn draw(
+    item: &QueueItem,
+    position: usize,
+    total: usize,
+    prompt: &str,
+    status: &str,
+) -> anyhow::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let (width, height) = (usize::from(columns), usize::from(rows));
+
+    let mut out = stdout();
+    queue!(out, Clear(ClearType::All))?;
+    let lines = item_lines(item, position, total, width);
+    for (row, line) in lines.iter().take(height.saturating_sub(2)).enumerate() {
+        let line = line.chars().take(width).collect::<String>();
+        queue!(out, MoveTo(0, row as u16), Print(line))?;
+    }
+    for (offset, line) in [prompt, status].iter().enumerate() {
+        let row = (height + offset).saturating_sub(2) as u16;
+        let line = line.chars().take(width).collect::<String>();
+        queue!(out, MoveTo(0, row), Print(line))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn read_key() -> anyhow::Result<KeyEvent> {
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+fn is_quit(key: &KeyEvent) -> bool {
+    matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+        || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+}
+
+// asks for the budget, prefilled with the one stored; None when the reviewer backs out
+fn read_budget(item: &QueueItem, position: usize, total: usize) -> anyhow::Result<Option<i32>> {
+    let mut input = item
+        .issue
+        .budget
+        .map(|budget| budget.to_string())
+        .unwrap_or_default();
+    let mut status = String::new();
+
+    loop {
+        let prompt = format!("budget: {input}_   enter approves, esc cancels");
+        draw(item, position, total, &prompt, &status)?;
+        match read_key()?.code {
+            KeyCode::Char(c) if c.is_ascii_digit() => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => match input.parse::<i32>() {
+                Ok(budget) => return Ok(Some(budget)),
+                Err(_) => status = String::from("the budget must be a whole number"),
+            },
+            KeyCode::Esc => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+// walks the campaign's queued issues one by one; skipped issues stay queued for the next run
+pub async fn run_review_queue(
+    pool: &PgPool,
+    campaign_id: &str,
+    reviewer: &str,
+) -> anyhow::Result<ReviewSummary> {
+    let mut summary = ReviewSummary::default();
+    let issue_ids = list_queue_issue_ids(pool, campaign_id).await?;
+    if issue_ids.is_empty() {
+        return Ok(summary);
+    }
+
+    let _guard = TerminalGuard::enter()?;
+    let total = issue_ids.len();
+    let mut status = String::new();
+    for (index, issue_id) in issue_ids.iter().enumerate() {
+        // gone since the queue was listed
+        let Some(item) = load_queue_item(pool, issue_id).await? else {
+            continue;
+        };
+        let position = index + 1;
+
+        loop {
+            draw(&item, position, total, KEYS_HELP, &status)?;
+            let key = read_key()?;
+            if is_quit(&key) {
+                return Ok(summary);
+            }
+            match key.code {
+                KeyCode::Char('a') => {
+                    let Some(budget) = read_budget(&item, position, total)? else {
+                        continue;
+                    };
+                    // over budget and the like keep the issue on screen with the reason
+                    match approve_project_per_issue(pool, issue_id, budget, true, reviewer).await {
+                        Ok(()) => {
+                            summary.approved += 1;
+                            status = format!("approved {issue_id} with {budget}");
+                            break;
+                        }
+                        Err(e) => status = format!("not approved: {e}"),
+                    }
+                }
+                KeyCode::Char('d') => {
+                    decline_issue(pool, issue_id, reviewer).await?;
+                    summary.declined += 1;
+                    status = format!("declined {issue_id}");
+                    break;
+                }
+                KeyCode::Char('s') | KeyCode::Right => {
+                    summary.skipped += 1;
+                    status = format!("skipped {issue_id}");
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,265 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::issues_tracker_local::github_http_get;
+use crate::pull_files::PullFile;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+// pull_requests scoring at least this are treated as spam by eligibility checks
+pub const DEFAULT_SPAM_THRESHOLD: i32 = 50;
+
+pub const TINY_ADDITIONS: i32 = 3;
+pub const NEW_ACCOUNT_DAYS: i64 = 30;
+// pull_requests to this many different owners within BURST_WINDOW_HOURS of each other
+pub const BURST_OWNER_COUNT: i64 = 5;
+pub const BURST_WINDOW_HOURS: i64 = 24;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpamSignals {
+    pub files: Vec<PullFile>,
+    pub whitespace_only: bool,
+    pub additions: i32,
+    pub opened_at: DateTime<Utc>,
+    pub account_created_at: Option<DateTime<Utc>>, // None while the author's profile is unknown
+    pub owners_in_window: i64, // distinct repo owners the author opened pull_requests with
+    pub same_title_count: i64, // other pull_requests carrying the same title
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SpamScore {
+    pub score: i32,
+    pub reasons: Vec<String>,
+}
+
+impl SpamScore {
+    fn add(&mut self, weight: i32, reason: String) {
+        self.score += weight;
+        self.reasons.push(reason);
+    }
+
+    pub fn is_spam(&self, threshold: i32) -> bool {
+        self.score >= threshold
+    }
+}
+
+pub fn is_readme(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .is_some_and(|name| name.to_ascii_lowercase().starts_with("readme"))
+}
+
+// every signal adds its weight, the reasons say which ones fired
+pub fn score_pull_request(signals: &SpamSignals) -> SpamScore {
+    let mut score = SpamScore::default();
+
+    if signals.whitespace_only {
+        score.add(40, String::from("whitespace-only diff"));
+    }
+    if !signals.files.is_empty() && signals.files.iter().all(|file| is_readme(&file.path)) {
+        score.add(30, String::from("only README files changed"));
+    }
+    if signals.additions <= TINY_ADDITIONS {
+        score.add(20, format!("{} lines added", signals.additions));
+    }
+    if let Some(created_at) = signals.account_created_at {
+        let age = signals.opened_at - created_at;
+        if age < Duration::days(NEW_ACCOUNT_DAYS) {
+            score.add(20, format!("account {} days old", age.num_days()));
+        }
+    }
+    if signals.owners_in_window >= BURST_OWNER_COUNT {
+        score.add(
+            30,
+            format!(
+                "pull_requests to {} owners within {}h",
+                signals.owners_in_window, BURST_WINDOW_HOURS
+            ),
+        );
+    }
+    if signals.same_title_count > 0 {
+        score.add(
+            20,
+            format!(
+                "title shared with {} other pull_requests",
+                signals.same_title_count
+            ),
+        );
+    }
+
+    score
+}
+
+// screens the file stats before the diff is downloaded: a whitespace-only change removes a line
+// for every non-blank line it adds, so a file adding more than a few lines without deleting any
+// rules it out
+pub fn may_be_whitespace_only(files: &[PullFile]) -> bool {
+    !files.is_empty()
+        && files
+            .iter()
+            .all(|file| file.deletions > 0 || file.additions <= TINY_ADDITIONS)
+}
+
+// true when the removed and added lines of a unified diff are the same once whitespace is
+// dropped, blank lines included
+pub fn is_whitespace_only_patch(patch: &str) -> bool {
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+
+    for line in patch.lines() {
+        if line.starts_with("---") || line.starts_with("+++") {
+            continue;
+        }
+        let target = match line.chars().next() {
+            Some('-') => &mut removed,
+            Some('+') => &mut added,
+            _ => continue,
+        };
+        let stripped = line[1..]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>();
+        if !stripped.is_empty() {
+            target.push(stripped);
+        }
+    }
+
+    removed.sort();
+    added.sort();
+    removed == added
+}
+
+// GraphQL has no patches, so the diff comes from the REST files endpoint; files GitHub sends
+// without a patch (binary or too large) count as real changes
+pub async fn is_whitespace_only_diff(pull_url: &str) -> anyhow::Result<bool> {
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct File {
+        filename: String,
+        patch: Option<String>,
+    }
+
+    let api_url = pull_url
+        .replacen("https://github.com/", "https://api.github.com/repos/", 1)
+        .replacen("/pull/", "/pulls/", 1);
+
+    let mut seen_any = false;
+    // the endpoint lists at most 3000 files, 30 pages of 100
+    for page in 1..=30 {
+        let response_body = github_http_get(&format!("{api_url}/files?per_page=100&page={page}"))
+            .await
+            .map_err(|e| anyhow!("Failed to get files of {}: {}", pull_url, e))?;
+
+        let files: Vec<File> = serde_json::from_slice(&response_body)
+            .map_err(|e| anyhow!("Failed to deserialize response: {}", e))?;
+
+        for file in &files {
+            match &file.patch {
+                Some(patch) if is_whitespace_only_patch(patch) => seen_any = true,
+                _ => return Ok(false),
+            }
+        }
+
+        if files.len() < 100 {
+            break;
+        }
+    }
+
+    Ok(seen_any)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, additions: i32, deletions: i32) -> PullFile {
+        PullFile {
+            path: path.to_string(),
+            additions,
+            deletions,
+        }
+    }
+
+    fn signals() -> SpamSignals {
+        let opened_at = "2023-10-10T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        SpamSignals {
+            files: vec![file("src/main.rs", 40, 10)],
+            whitespace_only: false,
+            additions: 40,
+            opened_at,
+            account_created_at: Some(opened_at - Duration::days(400)),
+            owners_in_window: 1,
+            same_title_count: 0,
+        }
+    }
+
+    #[test]
+    fn an_ordinary_pull_request_scores_zero() {
+        let score = score_pull_request(&signals());
+        assert_eq!(score.score, 0);
+        assert!(score.reasons.is_empty());
+        assert!(!score.is_spam(DEFAULT_SPAM_THRESHOLD));
+    }
+
+    #[test]
+    fn signals_add_up_past_the_threshold() {
+        let mut signals = signals();
+        signals.files = vec![file("README.md", 1, 0), file("docs/readme.txt", 1, 0)];
+        signals.additions = 2;
+        signals.whitespace_only = true;
+
+        let score = score_pull_request(&signals);
+        assert_eq!(score.score, 90);
+        assert_eq!(
+            score.reasons,
+            vec![
+                "whitespace-only diff",
+                "only README files changed",
+                "2 lines added"
+            ]
+        );
+        assert!(score.is_spam(DEFAULT_SPAM_THRESHOLD));
+    }
+
+    #[test]
+    fn new_accounts_bursts_and_shared_titles_are_scored() {
+        let mut signals = signals();
+        signals.account_created_at = Some(signals.opened_at - Duration::days(3));
+        signals.owners_in_window = BURST_OWNER_COUNT;
+        signals.same_title_count = 2;
+
+        let score = score_pull_request(&signals);
+        assert_eq!(score.score, 70);
+        assert_eq!(
+            score.reasons,
+            vec![
+                "account 3 days old",
+                "pull_requests to 5 owners within 24h",
+                "title shared with 2 other pull_requests"
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_account_age_is_not_scored() {
+        let mut signals = signals();
+        signals.account_created_at = None;
+        assert_eq!(score_pull_request(&signals).score, 0);
+    }
+
+    #[test]
+    fn whitespace_patches_ignore_indentation_and_blank_lines() {
+        let patch = "@@ -1,2 +1,3 @@\n-fn main() {\n+fn  main()  {\n+\n     body();";
+        assert!(is_whitespace_only_patch(patch));
+        assert!(!is_whitespace_only_patch("@@ -1 +1 @@\n-a\n+b"));
+    }
+
+    #[test]
+    fn file_stats_rule_out_whitespace_only_changes() {
+        assert!(may_be_whitespace_only(&[file("a.rs", 20, 20)]));
+        assert!(may_be_whitespace_only(&[file("README.md", 1, 0)]));
+        assert!(!may_be_whitespace_only(&[
+            file("a.rs", 20, 20),
+            file("b.rs", 20, 0)
+        ]));
+        assert!(!may_be_whitespace_only(&[]));
+    }
+}
@@ -0,0 +1,265 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+pub const DEFAULT_STALE_DAYS: i64 = 14;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StaleClaim {
+    pub issue_id: String,
+    pub assignee: String,
+    pub assigned_at: DateTime<Utc>,
+    pub last_activity_at: DateTime<Utc>, // latest of assignment, assignee comment or reference
+    pub days_idle: i64,
+}
+
+// assigned issues without a linked pull_request where the assignee has neither commented nor
+// referenced the issue from a commit or pull_request for `window`
+pub async fn find_stale_claims(pool: &PgPool, window: Duration) -> anyhow::Result<Vec<StaleClaim>> {
+    let recs = sqlx::query!(
+        r#"
+        SELECT a.issue_id AS "issue_id!", a.assignee AS "assignee!", a.assigned_at AS "assigned_at!",
+            GREATEST(
+                a.assigned_at,
+                (SELECT MAX(c.created_at) FROM comments c
+                    WHERE c.issue_id = a.issue_id AND c.creator = a.assignee AND NOT c.deleted),
+                (SELECT MAX(r.referenced_at) FROM issue_references r
+                    WHERE r.issue_id = a.issue_id AND r.actor = a.assignee)
+            ) AS "last_activity_at!"
+        FROM (
+            SELECT i.issue_id, i.issue_assignee AS assignee,
+                (SELECT MAX(e.created_at) FROM issue_assignment_events e
+                    WHERE e.issue_id = i.issue_id
+                        AND e.assignee = i.issue_assignee
+                        AND e.event = 'assigned') AS assigned_at
+            FROM issues i
+            WHERE i.issue_assignee IS NOT NULL
+                AND i.issue_linked_pr IS NULL
+                AND i.issue_status IS DISTINCT FROM 'closed'
+                AND NOT EXISTS (
+                    SELECT 1 FROM pull_issue_links l
+                    WHERE l.issue_id = i.issue_id AND l.origin <> 'mention'
+                )
+        ) a
+        WHERE a.assigned_at IS NOT NULL
+        ORDER BY a.issue_id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let now = Utc::now();
+    let claims = recs
+        .into_iter()
+        .filter(|r| now - r.last_activity_at >= window)
+        .map(|r| StaleClaim {
+            days_idle: (now - r.last_activity_at).num_days(),
+            issue_id: r.issue_id,
+            assignee: r.assignee,
+            assigned_at: r.assigned_at,
+            last_activity_at: r.last_activity_at,
+        })
+        .collect();
+
+    Ok(claims)
+}
+
+pub async fn mark_stale_claims(pool: &PgPool, claims: &[StaleClaim]) -> anyhow::Result<()> {
+    let issue_ids = claims
+        .iter()
+        .map(|claim| claim.issue_id.clone())
+        .collect::<Vec<String>>();
+
+    sqlx::query!(
+        r#"
+        UPDATE issues
+        SET issue_status = 'stale', stale_at = NOW()
+        WHERE issue_id = ANY($1) AND issue_status IS DISTINCT FROM 'stale'
+        "#,
+        &issue_ids,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// stale issues whose claim moved on go back to open: unassigned, assigned again since they were
+// flagged, or linked to a pull_request; `issue_id` limits it to one issue
+pub async fn clear_stale_claims(pool: &PgPool, issue_id: Option<&str>) -> anyhow::Result<u64> {
+    let rec = sqlx::query!(
+        r#"
+        UPDATE issues i
+        SET issue_status = 'open', stale_at = NULL
+        WHERE i.issue_status = 'stale'
+            AND ($1::VARCHAR IS NULL OR i.issue_id = $1)
+            AND (
+                i.issue_assignee IS NULL
+                OR i.issue_linked_pr IS NOT NULL
+                OR EXISTS (
+                    SELECT 1 FROM pull_issue_links l
+                    WHERE l.issue_id = i.issue_id AND l.origin <> 'mention'
+                )
+                OR EXISTS (
+                    SELECT 1 FROM issue_assignment_events e
+                    WHERE e.issue_id = i.issue_id
+                        AND e.event = 'assigned'
+                        AND e.created_at > i.stale_at
+                )
+            )
+        "#,
+        issue_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(rec.rows_affected())
+}
+
+// the job: releases the claims that moved on, finds the idle ones, flags them as stale and hands
+// them back for listing
+pub async fn run_stale_claim_check(
+    pool: &PgPool,
+    window_days: i64,
+) -> anyhow::Result<Vec<StaleClaim>> {
+    clear_stale_claims(pool, None).await?;
+    let claims = find_stale_claims(pool, Duration::days(window_days)).await?;
+    mark_stale_claims(pool, &claims).await?;
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::BotFilter;
+    use crate::campaigns::DEFAULT_CAMPAIGN_ID;
+    use crate::db_updater_local::refresh_issue_assignee;
+
+    const IDLE_ISSUE: &str = "https://github.com/owner/repo/issues/1";
+    const BUSY_ISSUE: &str = "https://github.com/owner/repo/issues/2";
+    const FRESH_ISSUE: &str = "https://github.com/owner/repo/issues/3";
+
+    async fn record_event(
+        pool: &PgPool,
+        issue_id: &str,
+        assignee: &str,
+        event: &str,
+        days_ago: i32,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO issue_assignment_events (event_id, issue_id, assignee, actor, event, created_at)
+            VALUES ($1, $2, $3, 'maintainer', $4, NOW() - make_interval(days => $5))
+            "#,
+        )
+        .bind(format!("{issue_id}/{event}/{assignee}/{days_ago}"))
+        .bind(issue_id)
+        .bind(assignee)
+        .bind(event)
+        .bind(days_ago)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn seed_claim(
+        pool: &PgPool,
+        issue_id: &str,
+        assignee: &str,
+        assigned_days_ago: i32,
+    ) -> anyhow::Result<()> {
+        sqlx::query("INSERT INTO contributors (login) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(assignee)
+            .execute(pool)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO issues (issue_id, project_id, issue_title, issue_description, campaign_id, issue_assignee)
+            VALUES ($1, 'https://github.com/owner/repo', 'title', 'body', $2, $3)
+            "#,
+        )
+        .bind(issue_id)
+        .bind(DEFAULT_CAMPAIGN_ID)
+        .bind(assignee)
+        .execute(pool)
+        .await?;
+        record_event(pool, issue_id, assignee, "assigned", assigned_days_ago).await
+    }
+
+    async fn status(pool: &PgPool, issue_id: &str) -> anyhow::Result<Option<String>> {
+        let status = sqlx::query_scalar("SELECT issue_status FROM issues WHERE issue_id = $1")
+            .bind(issue_id)
+            .fetch_one(pool)
+            .await?;
+        Ok(status)
+    }
+
+    #[sqlx::test]
+    async fn flags_only_claims_idle_for_the_window(pool: PgPool) -> anyhow::Result<()> {
+        seed_claim(&pool, IDLE_ISSUE, "alice", 30).await?;
+        seed_claim(&pool, BUSY_ISSUE, "alice", 30).await?;
+        seed_claim(&pool, FRESH_ISSUE, "bob", 3).await?;
+        // the assignee commenting counts as activity
+        sqlx::query(
+            r#"
+            INSERT INTO comments (comment_id, issue_id, creator, content, created_at)
+            VALUES ('comment-1', $1, 'alice', 'on it', NOW() - INTERVAL '2 days')
+            "#,
+        )
+        .bind(BUSY_ISSUE)
+        .execute(&pool)
+        .await?;
+
+        let claims = run_stale_claim_check(&pool, DEFAULT_STALE_DAYS).await?;
+
+        assert_eq!(
+            claims
+                .iter()
+                .map(|claim| claim.issue_id.as_str())
+                .collect::<Vec<&str>>(),
+            vec![IDLE_ISSUE]
+        );
+        assert_eq!(claims[0].assignee, "alice");
+        assert!(claims[0].days_idle >= DEFAULT_STALE_DAYS);
+        assert_eq!(status(&pool, IDLE_ISSUE).await?.as_deref(), Some("stale"));
+        assert_eq!(status(&pool, BUSY_ISSUE).await?, None);
+        assert_eq!(status(&pool, FRESH_ISSUE).await?, None);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn a_new_assignment_releases_the_flag(pool: PgPool) -> anyhow::Result<()> {
+        seed_claim(&pool, IDLE_ISSUE, "alice", 30).await?;
+        run_stale_claim_check(&pool, DEFAULT_STALE_DAYS).await?;
+        assert_eq!(status(&pool, IDLE_ISSUE).await?.as_deref(), Some("stale"));
+
+        record_event(&pool, IDLE_ISSUE, "alice", "unassigned", 0).await?;
+        record_event(&pool, IDLE_ISSUE, "bob", "assigned", 0).await?;
+        refresh_issue_assignee(&pool, IDLE_ISSUE, &BotFilter::default()).await?;
+
+        assert_eq!(status(&pool, IDLE_ISSUE).await?.as_deref(), Some("open"));
+        assert!(run_stale_claim_check(&pool, DEFAULT_STALE_DAYS)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn a_linked_pull_request_releases_the_flag(pool: PgPool) -> anyhow::Result<()> {
+        seed_claim(&pool, IDLE_ISSUE, "alice", 30).await?;
+        run_stale_claim_check(&pool, DEFAULT_STALE_DAYS).await?;
+
+        sqlx::query("UPDATE issues SET issue_linked_pr = $2 WHERE issue_id = $1")
+            .bind(IDLE_ISSUE)
+            .bind("https://github.com/owner/repo/pull/9")
+            .execute(&pool)
+            .await?;
+
+        assert!(run_stale_claim_check(&pool, DEFAULT_STALE_DAYS)
+            .await?
+            .is_empty());
+        assert_eq!(status(&pool, IDLE_ISSUE).await?.as_deref(), Some("open"));
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::campaigns::Campaign;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+// GitHub search stops at 1000 results, 10 pages of 100
+pub const SEARCH_PAGE_LIMIT: i32 = 10;
+
+// windows fetched at the same time unless SYNC_CONCURRENCY or --concurrency say otherwise
+pub const DEFAULT_SYNC_CONCURRENCY: u16 = 4;
+
+// rate limit points set aside for each window fetched at the same time; a page of pull requests
+// spends a few points on the search and more on follow-up queries for reviews, files and checks
+pub const RATE_LIMIT_POINTS_PER_WINDOW: i64 = 250;
+
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[sqlx(type_name = "sync_run_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum SyncRunStatus {
+    Running,
+    Failed,
+    Finished,
+}
+
+// which search a window runs and so how its results are stored
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "sync_search", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SyncSearch {
+    OpenIssues,
+    ClosedIssues,
+    PullRequests,
+}
+
+impl SyncSearch {
+    pub fn queries(&self, campaign: &Campaign) -> Vec<String> {
+        match self {
+            SyncSearch::OpenIssues => campaign.issue_queries(true),
+            SyncSearch::ClosedIssues => campaign.issue_queries(false),
+            SyncSearch::PullRequests => campaign.pull_request_queries(),
+        }
+    }
+}
+
+// "2024-10-01..2024-10-06" -> ("2024-10-01..2024-10-03", "2024-10-04..2024-10-06"), None for a
+// single day
+fn split_date_range(range: &str) -> Option<(String, String)> {
+    let (start, end) = range.split_once("..")?;
+    let start = NaiveDate::parse_from_str(start, "%Y-%m-%d").ok()?;
+    let end = NaiveDate::parse_from_str(end, "%Y-%m-%d").ok()?;
+    if end <= start {
+        return None;
+    }
+
+    let middle = start + Duration::days((end - start).num_days() / 2);
+    let range = |from: NaiveDate, to: NaiveDate| {
+        format!("{}..{}", from.format("%Y-%m-%d"), to.format("%Y-%m-%d"))
+    };
+    Some((range(start, middle), range(middle + Duration::days(1), end)))
+}
+
+// one search query of a run and how far it got
+#[derive(Clone, Debug)]
+pub struct SyncWindow {
+    pub window_index: i32,
+    pub search: SyncSearch,
+    pub query: String,
+    pub cursor: Option<String>, // where the next page starts
+    pub pages: i32,
+    pub finished: bool,
+    pub since: Option<DateTime<Utc>>, // the query's watermark when the run was planned
+    pub max_updated_at: Option<DateTime<Utc>>, // newest updatedAt fetched so far
+    pub base_query: Option<String>,   // the planned query a window split off at the cap belongs to
+}
+
+impl SyncWindow {
+    // what is sent to GitHub, the watermark narrows the query to what changed since the last run
+    pub fn search_query(&self) -> String {
+        match self.since {
+            Some(since) => format!(
+                "{} updated:>={}",
+                self.query,
+                since.format("%Y-%m-%dT%H:%M:%SZ")
+            ),
+            None => self.query.clone(),
+        }
+    }
+
+    // the query the window's watermark is kept under, the one it was planned for
+    pub fn watermark_query(&self) -> &str {
+        self.base_query.as_deref().unwrap_or(&self.query)
+    }
+
+    // a window with more results than the search returns is fetched again as two windows, each
+    // covering half of its created: range; None once the range is down to a single day
+    pub fn split(&self, next_index: &AtomicI32) -> Option<[SyncWindow; 2]> {
+        let mut halves = None;
+        let terms = self
+            .query
+            .split(' ')
+            .map(
+                |term| match term.strip_prefix("created:").and_then(split_date_range) {
+                    Some((first, second)) if halves.is_none() => {
+                        halves = Some((first, second));
+                        None
+                    }
+                    _ => Some(term),
+                },
+            )
+            .collect::<Vec<Option<&str>>>();
+        let (first, second) = halves?;
+
+        let window = |range: String| {
+            let query = terms
+                .iter()
+                .map(|term| term.map_or_else(|| format!("created:{range}"), str::to_string))
+                .collect::<Vec<String>>()
+                .join(" ");
+            SyncWindow {
+                window_index: next_index.fetch_add(1, Ordering::SeqCst),
+                search: self.search,
+                query,
+                cursor: None,
+                pages: 0,
+                finished: false,
+                since: self.since,
+                max_updated_at: None,
+                base_query: Some(self.watermark_query().to_string()),
+            }
+        };
+        Some([window(first), window(second)])
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SyncRun {
+    pub run_id: i32,
+    pub campaign_id: String,
+    pub target: String,
+    pub status: SyncRunStatus,
+    pub windows: Vec<SyncWindow>,
+}
+
+impl SyncRun {
+    pub fn pending_windows(&self) -> impl Iterator<Item = &SyncWindow> {
+        self.windows.iter().filter(|window| !window.finished)
+    }
+
+    // where the windows split off during the run are numbered from
+    pub fn next_window_index(&self) -> AtomicI32 {
+        AtomicI32::new(
+            self.windows
+                .iter()
+                .map(|window| window.window_index + 1)
+                .max()
+                .unwrap_or(0),
+        )
+    }
+}
+
+// the windows of a new run, every query of the first search before the next search starts;
+// `watermarks` maps a query to where the last finished run left it
+pub fn plan_windows(
+    campaign: &Campaign,
+    searches: &[SyncSearch],
+    watermarks: &HashMap<String, DateTime<Utc>>,
+) -> Vec<SyncWindow> {
+    searches
+        .iter()
+        .flat_map(|search| {
+            search
+                .queries(campaign)
+                .into_iter()
+                .map(move |query| (*search, query))
+        })
+        .enumerate()
+        .map(|(index, (search, query))| SyncWindow {
+            window_index: index as i32,
+            search,
+            since: watermarks.get(&query).copied(),
+            query,
+            cursor: None,
+            pages: 0,
+            finished: false,
+            max_updated_at: None,
+            base_query: None,
+        })
+        .collect()
+}
+
+// the configured concurrency, lowered so the windows in flight fit the rate limit points left;
+// one window always runs, GitHub's errors then stop the run and `--resume` continues it
+pub fn window_concurrency(configured: usize, rate_limit_remaining: i64) -> usize {
+    let affordable =
+        usize::try_from(rate_limit_remaining / RATE_LIMIT_POINTS_PER_WINDOW).unwrap_or(0);
+    configured.min(affordable).max(1)
+}
+
+// node ids already written by a run, shared by its concurrent windows; an item found by both
+// the open and the closed issue search is still written by each since they store different things
+#[derive(Debug, Default)]
+pub struct SeenNodes {
+    seen: Mutex<HashSet<(SyncSearch, String)>>,
+}
+
+impl SeenNodes {
+    // true while no page holding the node has been stored for the search; two windows may both
+    // store a node they fetch at the same time, storing is idempotent
+    pub fn is_new(&self, search: SyncSearch, node_id: &str) -> bool {
+        !self
+            .seen
+            .lock()
+            .expect("seen nodes lock poisoned")
+            .contains(&(search, node_id.to_string()))
+    }
+
+    // only called once the nodes are stored, a failed page leaves them to be fetched again
+    pub fn mark_seen<'a>(&self, search: SyncSearch, node_ids: impl IntoIterator<Item = &'a str>) {
+        self.seen.lock().expect("seen nodes lock poisoned").extend(
+            node_ids
+                .into_iter()
+                .map(|node_id| (search, node_id.to_string())),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(query: &str) -> SyncWindow {
+        SyncWindow {
+            window_index: 3,
+            search: SyncSearch::OpenIssues,
+            query: query.to_string(),
+            cursor: Some(String::from("abc")),
+            pages: 10,
+            finished: false,
+            since: None,
+            max_updated_at: None,
+            base_query: None,
+        }
+    }
+
+    #[test]
+    fn splits_the_created_range_in_half() {
+        let next_index = AtomicI32::new(7);
+        let [first, second] = window("label:a is:issue created:2024-10-01..2024-10-06 -label:spam")
+            .split(&next_index)
+            .expect("range of several days");
+
+        assert_eq!(
+            first.query,
+            "label:a is:issue created:2024-10-01..2024-10-03 -label:spam"
+        );
+        assert_eq!(
+            second.query,
+            "label:a is:issue created:2024-10-04..2024-10-06 -label:spam"
+        );
+        assert_eq!((first.window_index, second.window_index), (7, 8));
+        assert_eq!(first.cursor, None);
+        assert_eq!(first.pages, 0);
+        assert_eq!(next_index.load(Ordering::SeqCst), 9);
+    }
+
+    #[test]
+    fn splits_an_odd_range_down_to_single_days() {
+        let next_index = AtomicI32::new(0);
+        let [first, second] = window("created:2024-10-01..2024-10-02")
+            .split(&next_index)
+            .expect("two days");
+
+        assert_eq!(first.query, "created:2024-10-01..2024-10-01");
+        assert_eq!(second.query, "created:2024-10-02..2024-10-02");
+        assert_eq!(second.watermark_query(), "created:2024-10-01..2024-10-02");
+        assert!(first.split(&next_index).is_none());
+
+        let [first, second] = window("created:2024-10-30..2024-11-01")
+            .split(&next_index)
+            .expect("three days");
+        assert_eq!(first.query, "created:2024-10-30..2024-10-31");
+        assert_eq!(second.query, "created:2024-11-01..2024-11-01");
+
+        // windows split again keep reporting under the planned query
+        let [nested, _] = first.split(&next_index).expect("two days");
+        assert_eq!(nested.query, "created:2024-10-30..2024-10-30");
+        assert_eq!(nested.watermark_query(), "created:2024-10-30..2024-11-01");
+    }
+
+    #[test]
+    fn does_not_split_without_a_created_range() {
+        let next_index = AtomicI32::new(0);
+        assert!(window("label:a is:issue").split(&next_index).is_none());
+        assert!(window("created:>2024-10-01").split(&next_index).is_none());
+        assert_eq!(next_index.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn date_ranges_cover_the_campaign_without_overlap() {
+        let mut campaign = Campaign::hacktoberfest(2024);
+        assert_eq!(campaign.date_ranges().len(), 11);
+        assert_eq!(campaign.date_ranges()[0], "2024-10-01..2024-10-03");
+        assert_eq!(campaign.date_ranges()[10], "2024-10-31..2024-10-31");
+
+        campaign.window_days = 0;
+        assert_eq!(campaign.date_ranges().len(), 31);
+        assert_eq!(campaign.date_ranges()[4], "2024-10-05..2024-10-05");
+    }
+
+    #[test]
+    fn next_window_index_follows_the_highest() {
+        let run = SyncRun {
+            run_id: 1,
+            campaign_id: String::from("c"),
+            target: String::from("issues"),
+            status: SyncRunStatus::Running,
+            windows: vec![window("a"), window("b")],
+        };
+        assert_eq!(run.next_window_index().load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn nodes_are_seen_once_marked() {
+        let seen = SeenNodes::default();
+        assert!(seen.is_new(SyncSearch::OpenIssues, "I_1"));
+        assert!(seen.is_new(SyncSearch::OpenIssues, "I_1"));
+
+        seen.mark_seen(SyncSearch::OpenIssues, ["I_1", "I_2"]);
+        assert!(!seen.is_new(SyncSearch::OpenIssues, "I_1"));
+        assert!(!seen.is_new(SyncSearch::OpenIssues, "I_2"));
+        assert!(seen.is_new(SyncSearch::ClosedIssues, "I_1"));
+    }
+
+    #[test]
+    fn concurrency_fits_the_rate_limit() {
+        assert_eq!(window_concurrency(4, 5000), 4);
+        assert_eq!(window_concurrency(4, 2 * RATE_LIMIT_POINTS_PER_WINDOW), 2);
+        assert_eq!(window_concurrency(4, 0), 1);
+        assert_eq!(window_concurrency(4, -1), 1);
+        assert_eq!(window_concurrency(0, 5000), 1);
+    }
+}